@@ -0,0 +1,51 @@
+use apnoxide::{PushOption, PushType};
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use reqwest::header::HeaderMap;
+
+fn option() -> PushOption<'static> {
+    PushOption {
+        push_type: Some(PushType::Alert),
+        id: Some("EEEEEEEE-0000-0000-0000-000000000001"),
+        expiration: Some(1_700_000_000),
+        priority: Some(10),
+        topic: "com.example.app",
+        collapse_id: Some("collapse"),
+        ..PushOption::default()
+    }
+}
+
+/// Header construction parsing every name and value, as before the fast path.
+fn to_string_headers(option: PushOption) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(push_type) = option.push_type {
+        headers.insert("apns-push-type", push_type.as_str().parse().unwrap());
+    }
+    if let Some(id) = option.id {
+        headers.insert("apns-id", id.parse().unwrap());
+    }
+    if let Some(expiration) = option.expiration {
+        headers.insert("apns-expiration", expiration.to_string().parse().unwrap());
+    }
+    if let Some(priority) = option.priority {
+        headers.insert("apns-priority", priority.to_string().parse().unwrap());
+    }
+    if let Some(collapse_id) = option.collapse_id {
+        headers.insert("apns-collapse-id", collapse_id.parse().unwrap());
+    }
+    headers.insert("apns-topic", option.topic.parse().unwrap());
+    headers
+}
+
+fn bench_headers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("headers");
+    group.bench_function("try_from", |b| {
+        b.iter(|| HeaderMap::try_from(black_box(option())).unwrap())
+    });
+    group.bench_function("to_string", |b| {
+        b.iter(|| to_string_headers(black_box(option())))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_headers);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+use apnoxide::{Alert, Body, Notification, Payload, Sound, Title};
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use std::collections::HashMap;
+
+fn payload() -> Payload {
+    Payload::from(Notification {
+        alert: Some(
+            Alert::full()
+                .with_title(Title::Normal("New message".to_string()))
+                .with_body(Body::Normal("Are we still on for lunch?".to_string())),
+        ),
+        badge: Some(3),
+        sound: Some(Sound::Regular("default".to_string())),
+        thread_id: Some("conversation-42".to_string()),
+        ..Notification::default()
+    })
+    .with_custom(HashMap::from([("conversation", "42"), ("sender", "alice")]))
+    .unwrap()
+}
+
+fn bench_payload(c: &mut Criterion) {
+    let payload = payload();
+    c.bench_function("payload/to_vec", |b| {
+        b.iter(|| serde_json::to_vec(black_box(&payload)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_payload);
+criterion_main!(benches);
@@ -0,0 +1,143 @@
+use crate::{APNClientError, APNResponse};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Counts of outcomes from a batch of pushes, such as the results of
+/// [`APNClient::push_many`](crate::APNClient::push_many).
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Failures whose device token should be deleted.
+    pub remove_token: usize,
+    /// Failures worth sending again later.
+    pub retryable: usize,
+    /// Failures answered by APNs, keyed by reason.
+    pub by_reason: BTreeMap<String, usize>,
+}
+
+impl BatchSummary {
+    pub fn from_results(results: &[Result<APNResponse, APNClientError>]) -> Self {
+        let mut summary = Self {
+            total: results.len(),
+            ..Self::default()
+        };
+        for err in results.iter().filter_map(|result| result.as_ref().err()) {
+            summary.failed += 1;
+            if err.should_remove_token() {
+                summary.remove_token += 1;
+            }
+            if err.is_retryable() {
+                summary.retryable += 1;
+            }
+            if let Some(reason) = err.reason() {
+                *summary.by_reason.entry(reason.to_string()).or_default() += 1;
+            }
+        }
+        summary.succeeded = summary.total - summary.failed;
+        summary
+    }
+}
+
+impl Display for BatchSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pushes: {} succeeded, {} failed ({} to remove, {} to retry)",
+            self.total, self.succeeded, self.failed, self.remove_token, self.retryable
+        )?;
+        if !self.by_reason.is_empty() {
+            let reasons = self
+                .by_reason
+                .iter()
+                .map(|(reason, count)| format!("{}: {}", reason, count))
+                .collect::<Vec<_>>();
+            write!(f, " [{}]", reasons.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A [`BatchSummary`] together with the device tokens APNs reported as no longer valid, for
+/// the results of pushing to `tokens`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PushReport {
+    pub summary: BatchSummary,
+    /// Tokens to delete, in the order they were pushed to.
+    pub tokens_to_remove: Vec<String>,
+}
+
+impl PushReport {
+    /// Pairs each result with the token at the same index in `tokens`.
+    pub fn from_results<T: AsRef<str>>(
+        tokens: &[T],
+        results: &[Result<APNResponse, APNClientError>],
+    ) -> Self {
+        let tokens_to_remove = tokens
+            .iter()
+            .zip(results)
+            .filter(|(_, result)| matches!(result, Err(err) if err.should_remove_token()))
+            .map(|(token, _)| token.as_ref().to_string())
+            .collect();
+        Self {
+            summary: BatchSummary::from_results(results),
+            tokens_to_remove,
+        }
+    }
+}
+
+impl Display for PushReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.summary.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{apn_error, apn_response};
+    use crate::{BatchSummary, PushReport};
+
+    #[test]
+    fn test_summary_counts() {
+        let results = vec![
+            Ok(apn_response()),
+            Ok(apn_response()),
+            Err(apn_error(410, "Unregistered")),
+            Err(apn_error(400, "BadDeviceToken")),
+            Err(apn_error(429, "TooManyRequests")),
+            Err(apn_error(429, "TooManyRequests")),
+            Err(apn_error(400, "BadTopic")),
+        ];
+        let summary = BatchSummary::from_results(&results);
+
+        assert_eq!(7, summary.total);
+        assert_eq!(2, summary.succeeded);
+        assert_eq!(5, summary.failed);
+        assert_eq!(2, summary.remove_token);
+        assert_eq!(2, summary.retryable);
+        assert_eq!(Some(&2), summary.by_reason.get("TooManyRequests"));
+        assert_eq!(Some(&1), summary.by_reason.get("BadTopic"));
+        assert_eq!(
+            "7 pushes: 2 succeeded, 5 failed (2 to remove, 2 to retry) \
+             [BadDeviceToken: 1, BadTopic: 1, TooManyRequests: 2, Unregistered: 1]",
+            summary.to_string()
+        );
+    }
+
+    #[test]
+    fn test_push_report() {
+        let tokens = ["token-1", "token-2", "token-3", "token-4"];
+        let results = vec![
+            Ok(apn_response()),
+            Err(apn_error(410, "Unregistered")),
+            Err(apn_error(429, "TooManyRequests")),
+            Err(apn_error(400, "BadDeviceToken")),
+        ];
+        let report = PushReport::from_results(&tokens, &results);
+
+        assert_eq!(vec!["token-2", "token-4"], report.tokens_to_remove);
+        assert_eq!(BatchSummary::from_results(&results), report.summary);
+        assert_eq!(report.summary.to_string(), report.to_string());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{Alert, BuildError, Notification, Payload, PushOption, Sound};
+use serde::Serialize;
+use std::marker::PhantomData;
+
+/// [`PayloadBuilder`] mode for a visible notification.
+#[derive(Debug)]
+pub struct AlertMode;
+
+/// [`PayloadBuilder`] mode for a silent `content-available` update.
+#[derive(Debug)]
+pub struct BackgroundMode;
+
+/// Builds either a visible alert or a silent background push, never both.
+///
+/// The mode is picked up front with [`alert`](PayloadBuilder::alert) or
+/// [`background`](PayloadBuilder::background), and only the alert mode offers badge and sound,
+/// so a payload Apple would treat as neither can not be built.
+#[derive(Debug)]
+pub struct PayloadBuilder<M> {
+    payload: Payload,
+    mode: PhantomData<M>,
+}
+
+impl PayloadBuilder<AlertMode> {
+    pub fn alert(alert: Alert) -> Self {
+        Self::with_aps(Notification {
+            alert: Some(alert),
+            ..Notification::default()
+        })
+    }
+
+    pub fn with_badge(mut self, badge: u32) -> Self {
+        self.payload.aps.badge = Some(badge);
+        self
+    }
+
+    pub fn with_sound(mut self, sound: Sound) -> Self {
+        self.payload.aps.sound = Some(sound);
+        self
+    }
+
+    pub fn with_category(mut self, category: &str) -> Self {
+        self.payload.aps.category = Some(category.to_string());
+        self
+    }
+
+    /// See [`Notification::with_thread_id`].
+    pub fn with_thread_id(mut self, thread_id: impl Into<String>) -> Result<Self, BuildError> {
+        self.payload.aps = std::mem::take(&mut self.payload.aps).with_thread_id(thread_id)?;
+        Ok(self)
+    }
+
+    /// The payload, with [`PushOption::alert`] for `topic`.
+    pub fn build(self, topic: &str) -> Result<(Payload, PushOption<'_>), BuildError> {
+        self.payload.validate()?;
+        Ok((self.payload, PushOption::alert(topic)))
+    }
+}
+
+impl PayloadBuilder<BackgroundMode> {
+    pub fn background() -> Self {
+        Self::with_aps(Notification::silent())
+    }
+
+    /// The payload, with [`PushOption::background`] for `topic`.
+    pub fn build(self, topic: &str) -> Result<(Payload, PushOption<'_>), BuildError> {
+        self.payload.validate()?;
+        Ok((self.payload, PushOption::background(topic)))
+    }
+}
+
+impl<M> PayloadBuilder<M> {
+    fn with_aps(aps: Notification) -> Self {
+        Self {
+            payload: Payload::from(aps),
+            mode: PhantomData,
+        }
+    }
+
+    /// See [`Payload::with_custom`].
+    pub fn with_custom<T: Serialize>(mut self, custom: T) -> Result<Self, BuildError> {
+        self.payload = self.payload.with_custom(custom)?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Alert, PayloadBuilder, PushType, Sound};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_alert_mode() {
+        let (payload, option) = PayloadBuilder::alert(Alert::Body("Hello".to_string()))
+            .with_badge(2)
+            .with_sound(Sound::Regular("default".to_string()))
+            .build("com.example.app")
+            .unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "aps": { "alert": "Hello", "badge": 2, "sound": "default" },
+            }),
+            serde_json::to_value(&payload).unwrap()
+        );
+        assert_eq!(Some(PushType::Alert), option.push_type);
+        assert_eq!(Some(10), option.priority);
+
+        let empty = PayloadBuilder::alert(Alert::Body(String::new())).build("com.example.app");
+        assert!(empty.is_err());
+    }
+
+    #[test]
+    fn test_background_mode() {
+        let (payload, option) = PayloadBuilder::background()
+            .with_custom(HashMap::from([("sync", true)]))
+            .unwrap()
+            .build("com.example.app")
+            .unwrap();
+        assert_eq!(
+            serde_json::json!({ "aps": { "content-available": 1 }, "sync": true }),
+            serde_json::to_value(&payload).unwrap()
+        );
+        assert_eq!(Some(PushType::Background), option.push_type);
+        assert_eq!(Some(5), option.priority);
+    }
+}
@@ -1,12 +1,34 @@
-use crate::client::APNClientError::{HeaderError, InitializeError, SignError};
-use crate::APNClientError::{APNError, InvalidResponseError};
-use crate::{Endpoint, Payload, PushOption};
-use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
-use reqwest::header::ToStrError;
+use crate::APNClientError::{APNError, DuplicateIdError, InvalidResponseError, RequestPathError};
+use crate::client::APNClientError::{HTTPError, HeaderError, InitializeError, SignError};
+use crate::collapse::truncate_collapse_id;
+use crate::connect::{ConnectLayer, DnsError, DnsResolver};
+use crate::rate_limit::RateLimiter;
+use crate::types::{APNS_COLLAPSE_ID, APNS_ID, APNS_PUSH_TYPE, APNS_TOPIC};
+use crate::{
+    AdaptiveConcurrency, Alert, ApnReason, ApnStatus, Body, BuildError, Clock, CollapseStrategy,
+    DerKey, Endpoint, KeySource, Metrics, NoCollapse, NoopMetrics, Notification, Payload, PemKey,
+    PushEvent, PushOption, PushOptionError, RateLimit, RetryPolicy, SystemClock, Title, Topic,
+};
+use bytes::Bytes;
+use futures_util::StreamExt;
+use futures_util::future::{Either, select};
+use futures_util::stream::FuturesUnordered;
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use reqwest::Version;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderValue, ToStrError};
 use serde::{Deserialize, Serialize};
-use snafu::{OptionExt, ResultExt, Snafu};
+use snafu::{OptionExt, ResultExt, Snafu, ensure};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::net::SocketAddr;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
 
 #[derive(Debug, Snafu)]
 #[non_exhaustive]
@@ -15,6 +37,16 @@ pub enum APNClientError {
     InitializeError {
         msg: String,
     },
+    /// The key parsed, but can not sign the ES256 tokens APNs requires, e.g. a P-384 key.
+    #[snafu(display("Private key is not a valid P-256 key, which APNs requires for ES256"))]
+    UnsupportedKeyError,
+    /// The key is not a PKCS #8 encoded EC key.
+    #[snafu(display("Unable to decode private key"))]
+    InvalidKeyError,
+    #[snafu(display("Missing required config field {}", field))]
+    MissingConfigError {
+        field: &'static str,
+    },
     #[snafu(display("Error when signing token: {}", msg))]
     SignError {
         msg: String,
@@ -27,23 +59,290 @@ pub enum APNClientError {
     },
     #[snafu(display("Unable to parse header"))]
     HeaderError,
+    #[snafu(display("Invalid push option: {}", source))]
+    InvalidOptionError {
+        source: PushOptionError,
+    },
+    /// The response could not be parsed; `response` keeps the `apns-id` if it was readable.
     #[snafu(display("Can not parse APN server response"))]
-    InvalidResponseError,
-    #[snafu(display("Error from APN server: {}", error.reason))]
+    InvalidResponseError {
+        response: Option<Box<APNResponse>>,
+    },
+    /// APNs answered `404` or `405`, which points at a malformed device token or endpoint rather
+    /// than a rejected push. `path` has the device token redacted.
+    #[snafu(display(
+        "APN server rejected request path {} with status {}; check the device token and endpoint",
+        path,
+        status
+    ))]
+    RequestPathError {
+        status: ApnStatus,
+        path: String,
+        response: Option<Box<APNResponse>>,
+        error: Option<Box<APNErrorResponse>>,
+    },
+    /// APNs rejected the push. For `BadCollapseId` and `BadMessageId`, `rejected` names the
+    /// header APNs blamed and the value sent for it.
+    #[snafu(display(
+        "Error from APN server: {} ({}). {}{}",
+        error.reason,
+        error.kind().explanation(),
+        error.kind().remediation(),
+        rejected
+            .as_ref()
+            .map(|rejected| format!(" Sent {} {:?}.", rejected.field, rejected.value))
+            .unwrap_or_default()
+    ))]
     APNError {
-        response: APNResponse,
-        status: u16,
-        error: APNErrorResponse,
+        response: Box<APNResponse>,
+        status: ApnStatus,
+        error: Box<APNErrorResponse>,
+        rejected: Option<Box<RejectedOption>>,
     },
     ToStrError {
         source: ToStrError,
     },
+    #[snafu(display("Invalid payload: {}", source))]
+    InvalidPayloadError {
+        source: BuildError,
+    },
+    #[snafu(display("Invalid environment variable {}: {}", name, msg))]
+    EnvironmentError {
+        name: String,
+        msg: String,
+    },
+    #[snafu(display("Push did not complete before the deadline"))]
+    TimeoutError,
+    #[snafu(display("Push was cancelled"))]
+    CancelledError,
+    #[snafu(display("Topic {} is not in the allowed topics", topic))]
+    TopicNotAllowedError {
+        topic: String,
+    },
+    #[snafu(display("APN server did not return apns-unique-id for {}", id))]
+    MissingUniqueIdError {
+        id: String,
+    },
+    #[snafu(display("Duplicate apns-id in batch: {}", ids.join(", ")))]
+    DuplicateIdError {
+        ids: Vec<String>,
+    },
+}
+
+impl APNClientError {
+    /// Whether APNs reported the device token as invalid or no longer active.
+    pub fn should_remove_token(&self) -> bool {
+        match self {
+            APNError { status, error, .. } => {
+                *status == ApnStatus::Gone
+                    || matches!(
+                        error.kind(),
+                        ApnReason::BadDeviceToken | ApnReason::Unregistered
+                    )
+            }
+            _ => false,
+        }
+    }
+
+    /// The reason APNs gave for rejecting the push, if it answered with one.
+    pub fn reason(&self) -> Option<ApnReason> {
+        match self {
+            APNError { error, .. }
+            | RequestPathError {
+                error: Some(error), ..
+            } => Some(error.kind()),
+            _ => None,
+        }
+    }
+
+    /// Whether the same push may succeed if sent again later, without risking a second
+    /// delivery.
+    ///
+    /// A transport error is only retryable if the connection could not be made. Once the
+    /// request is sent, APNs may have accepted the push even if no answer arrives, and APNs does
+    /// not deduplicate by `apns-id`.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            APNError { status, .. } => matches!(
+                status,
+                ApnStatus::TooManyRequests
+                    | ApnStatus::InternalServerError
+                    | ApnStatus::ServiceUnavailable
+            ),
+            HTTPError { source } => source.is_connect(),
+            _ => false,
+        }
+    }
+
+    /// Which stage of the transport failed, for an error that never reached APNs.
+    pub fn transport_failure(&self) -> Option<TransportFailure> {
+        match self {
+            HTTPError { source } => Some(TransportFailure::classify(source)),
+            _ => None,
+        }
+    }
+}
+
+/// The stage a request failed in before APNs could answer, to tell an APNs outage apart from a
+/// local DNS or network problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransportFailure {
+    /// The APNs host name did not resolve.
+    Dns,
+    /// The TCP connection was refused or could not be made.
+    Connect,
+    /// The TLS handshake failed.
+    Tls,
+    /// The request timed out.
+    Timeout,
+    /// The request failed on an established connection.
+    Other,
+}
+
+impl TransportFailure {
+    fn classify(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            return Self::Timeout;
+        }
+        if !error.is_connect() {
+            return Self::Other;
+        }
+        let mut source = std::error::Error::source(error);
+        while let Some(cause) = source {
+            if cause.is::<DnsError>() {
+                return Self::Dns;
+            }
+            if let Some(error) = cause.downcast_ref::<io::Error>() {
+                return match error.kind() {
+                    io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::HostUnreachable
+                    | io::ErrorKind::NetworkUnreachable
+                    | io::ErrorKind::NetworkDown
+                    | io::ErrorKind::AddrNotAvailable
+                    | io::ErrorKind::TimedOut => Self::Connect,
+                    // The TLS stream reports handshake failures as other kinds of IO errors.
+                    _ => Self::Tls,
+                };
+            }
+            source = cause.source();
+        }
+        Self::Tls
+    }
 }
 
 #[derive(Debug)]
 pub struct APNResponse {
     pub id: String,
+    pub kind: APNResponseKind,
+    pub(crate) elapsed: Duration,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum APNResponseKind {
+    /// A device push, answered with `apns-id` only.
+    Device,
+    /// A broadcast push, which APNs additionally answers with `apns-unique-id`.
+    Broadcast { unique_id: String },
+}
+
+impl APNResponse {
+    pub fn is_broadcast(&self) -> bool {
+        matches!(self.kind, APNResponseKind::Broadcast { .. })
+    }
+
+    pub fn unique_id(&self) -> Option<&str> {
+        match &self.kind {
+            APNResponseKind::Device => None,
+            APNResponseKind::Broadcast { unique_id } => Some(unique_id),
+        }
+    }
+
+    /// The `apns-id` parsed as a UUID, or `None` if it is not one.
+    #[cfg(feature = "uuid")]
+    pub fn id_uuid(&self) -> Option<Uuid> {
+        Uuid::parse_str(&self.id).ok()
+    }
+
+    /// From just before the request was sent until APNs answered, for the last attempt when the
+    /// push was retried. Zero for a response not read from a push.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// The answer to a [broadcast push](APNClient::push_broadcast).
+///
+/// Device pushes keep returning [`APNResponse`], since APNs answers some of them, such as
+/// push-to-start Live Activities, with an `apns-unique-id` too.
+#[derive(Debug)]
+pub struct BroadcastResponse {
+    pub id: String,
+    /// The `apns-unique-id`, or `None` if APNs accepted the broadcast without one.
     pub unique_id: Option<String>,
+    pub(crate) elapsed: Duration,
+}
+
+impl BroadcastResponse {
+    /// See [`APNResponse::elapsed`].
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+impl From<APNResponse> for BroadcastResponse {
+    fn from(response: APNResponse) -> Self {
+        let unique_id = match response.kind {
+            APNResponseKind::Broadcast { unique_id } => Some(unique_id),
+            APNResponseKind::Device => None,
+        };
+        Self {
+            id: response.id,
+            unique_id,
+            elapsed: response.elapsed,
+        }
+    }
+}
+
+impl From<BroadcastResponse> for APNResponse {
+    fn from(response: BroadcastResponse) -> Self {
+        let kind = match response.unique_id {
+            Some(unique_id) => APNResponseKind::Broadcast { unique_id },
+            None => APNResponseKind::Device,
+        };
+        Self {
+            id: response.id,
+            kind,
+            elapsed: response.elapsed,
+        }
+    }
+}
+
+impl TryFrom<&HeaderMap> for APNResponse {
+    type Error = APNClientError;
+
+    fn try_from(headers: &HeaderMap) -> Result<Self, Self::Error> {
+        let id = String::from(
+            headers
+                .get("apns-id")
+                .context(InvalidResponseSnafu { response: None })?
+                .to_str()
+                .context(ToStrSnafu)?,
+        );
+        let kind = match headers.get("apns-unique-id") {
+            None => APNResponseKind::Device,
+            Some(value) => APNResponseKind::Broadcast {
+                unique_id: value.to_str().context(ToStrSnafu)?.to_string(),
+            },
+        };
+        Ok(Self {
+            id,
+            kind,
+            elapsed: Duration::ZERO,
+        })
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -52,11 +351,85 @@ pub struct APNErrorResponse {
     pub timestamp: Option<u64>,
 }
 
+/// Where the value of a header APNs rejected came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RejectedField {
+    /// [`PushOption::id`], or `PushOption::id_uuid` with the `uuid` feature.
+    Id,
+    /// [`PushOption::collapse_id`].
+    CollapseId,
+    /// The collapse id derived by the
+    /// [collapse strategy](APNClientConfig::with_collapse_strategy).
+    CollapseStrategy,
+}
+
+impl Display for RejectedField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RejectedField::Id => "PushOption::id",
+            RejectedField::CollapseId => "PushOption::collapse_id",
+            RejectedField::CollapseStrategy => "the collapse id from the collapse strategy",
+        })
+    }
+}
+
+/// The header APNs blamed for a `BadCollapseId` or `BadMessageId`, with the value sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedOption {
+    pub field: RejectedField,
+    pub value: String,
+}
+
+impl APNErrorResponse {
+    /// The `reason` parsed into the documented set of reasons.
+    pub fn kind(&self) -> ApnReason {
+        ApnReason::from(self.reason.as_str())
+    }
+}
+
+/// APNs rejects provider tokens issued more than one hour ago.
+const TOKEN_LIFETIME: Duration = Duration::from_secs(60 * 60);
+/// Re-sign once less than 40 minutes of validity remain, i.e. every 20 minutes.
+const DEFAULT_TOKEN_REFRESH_THRESHOLD: Duration = Duration::from_secs(60 * 40);
+/// Probe idle connections well within common NAT and load balancer idle timeouts.
+const DEFAULT_TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+/// The two ports APNs listens on, switched between by the port fallback.
+const APNS_PORTS: [u16; 2] = [443, 2197];
+
+const DEVICE_PATH: &str = "/3/device/";
+
+const HEALTH_CHECK_TOKEN: &str = "0";
+
 pub struct APNClientConfig {
     team_id: String,
     key_id: String,
     key: EncodingKey,
     endpoint: String,
+    clock: Arc<dyn Clock>,
+    metrics: Arc<dyn Metrics>,
+    collapse_strategy: Arc<dyn CollapseStrategy>,
+    token_refresh_threshold: Duration,
+    jwt_typ: Option<String>,
+    proxy: Option<reqwest::Proxy>,
+    tcp_nodelay: bool,
+    tcp_keepalive: Option<Duration>,
+    request_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    connections: usize,
+    port_fallback: bool,
+    fallback_ports: [u16; 2],
+    strict_priority: bool,
+    resolve: Option<SocketAddr>,
+    resolver: Option<Arc<dyn reqwest::dns::Resolve>>,
+    verify_on_build: bool,
+    topic: Option<String>,
+    default_push_option: PushOption<'static>,
+    allowed_topics: Option<HashSet<String>>,
+    retry_policy: Option<Arc<RetryPolicy>>,
+    timeout: Option<Duration>,
+    concurrency: Option<AdaptiveConcurrency>,
+    rate_limit: Option<RateLimit>,
 }
 
 #[derive(Serialize)]
@@ -65,6 +438,8 @@ pub struct APNTokenClaims {
     pub issuer_team_id: String,
     #[serde(rename = "iat")]
     pub issued_at: u64,
+    #[serde(rename = "exp")]
+    pub expires_at: u64,
 }
 
 impl APNClientConfig {
@@ -74,110 +449,2690 @@ impl APNClientConfig {
         key: &str,
         endpoint: Endpoint,
     ) -> Result<Self, APNClientError> {
-        let key = EncodingKey::from_ec_pem(key.as_bytes()).map_err(|_| InitializeError {
-            msg: "Unable to parse private key".to_string(),
-        })?;
-        Ok(Self {
+        Self::from_source(team_id, key_id, &PemKey::new(key), endpoint)
+    }
+
+    pub fn new_from_der(
+        team_id: &str,
+        key_id: &str,
+        der: &[u8],
+        endpoint: Endpoint,
+    ) -> Result<Self, APNClientError> {
+        Self::from_source(team_id, key_id, &DerKey::new(der), endpoint)
+    }
+
+    /// Loads the signing key from any [`KeySource`], e.g. a [`PemFile`](crate::PemFile).
+    pub fn from_source(
+        team_id: &str,
+        key_id: &str,
+        source: &(impl KeySource + ?Sized),
+        endpoint: Endpoint,
+    ) -> Result<Self, APNClientError> {
+        Ok(Self::from_key(team_id, key_id, source.load()?, endpoint))
+    }
+
+    fn from_key(team_id: &str, key_id: &str, key: EncodingKey, endpoint: Endpoint) -> Self {
+        Self {
             team_id: team_id.to_string(),
             key_id: key_id.to_string(),
             key,
             endpoint: endpoint.into(),
+            clock: Arc::new(SystemClock),
+            metrics: Arc::new(NoopMetrics),
+            collapse_strategy: Arc::new(NoCollapse),
+            token_refresh_threshold: DEFAULT_TOKEN_REFRESH_THRESHOLD,
+            proxy: None,
+            tcp_nodelay: true,
+            tcp_keepalive: Some(DEFAULT_TCP_KEEPALIVE),
+            request_timeout: None,
+            pool_max_idle_per_host: None,
+            connections: 1,
+            port_fallback: false,
+            fallback_ports: APNS_PORTS,
+            strict_priority: false,
+            resolve: None,
+            resolver: None,
+            verify_on_build: false,
+            topic: None,
+            default_push_option: PushOption::default(),
+            allowed_topics: None,
+            retry_policy: None,
+            timeout: None,
+            concurrency: None,
+            rate_limit: None,
+            jwt_typ: None,
+        }
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+
+    /// Derives a collapse id with `strategy` for pushes whose [`PushOption`] sets none.
+    pub fn with_collapse_strategy(mut self, strategy: impl CollapseStrategy + 'static) -> Self {
+        self.collapse_strategy = Arc::new(strategy);
+        self
+    }
+
+    /// Sets how much validity a cached token must have left to be reused.
+    ///
+    /// Tokens are valid for 60 minutes. The default of 40 minutes re-signs every 20 minutes,
+    /// which is as often as APNs allows before answering `TooManyProviderTokenUpdates`.
+    pub fn with_token_refresh_threshold(mut self, threshold: Duration) -> Self {
+        self.token_refresh_threshold = threshold;
+        self
+    }
+
+    /// Sets the `typ` header of the provider token, or leaves it out with `None`.
+    ///
+    /// Left out by default, since APNs documents only `alg` and `kid`. Set it to `"JWT"` for
+    /// intermediaries that check the header more strictly than APNs does.
+    pub fn with_jwt_typ(mut self, typ: Option<&str>) -> Self {
+        self.jwt_typ = typ.map(str::to_string);
+        self
+    }
+
+    /// Routes all traffic through the given proxy.
+    ///
+    /// APNs only speaks HTTP/2, so the proxy must support tunnelling it via `CONNECT`.
+    pub fn with_proxy(mut self, url: &str) -> Result<Self, APNClientError> {
+        self.proxy = Some(Self::parse_proxy(url)?);
+        Ok(self)
+    }
+
+    /// Like [`with_proxy`](Self::with_proxy), authenticating to the proxy with basic auth.
+    pub fn with_proxy_auth(
+        mut self,
+        url: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<Self, APNClientError> {
+        self.proxy = Some(Self::parse_proxy(url)?.basic_auth(username, password));
+        Ok(self)
+    }
+
+    /// Sets the `apns-topic` sent when a [`PushOption`] leaves its topic empty.
+    pub fn with_topic(mut self, topic: &str) -> Self {
+        self.topic = Some(topic.to_string());
+        self
+    }
+
+    pub fn topic(&self) -> Option<&str> {
+        self.topic.as_deref()
+    }
+
+    /// Rejects pushes to any topic outside `topics` with
+    /// [`TopicNotAllowedError`](APNClientError::TopicNotAllowedError) before they are sent.
+    ///
+    /// Meant for keys scoped to known apps, catching what APNs would answer with
+    /// `TopicDisallowed`. A bundle id also allows its suffixed topics, such as `.voip`. Without
+    /// an allow-list, topics are not checked locally.
+    pub fn with_allowed_topics<I, T>(mut self, topics: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.allowed_topics = Some(topics.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Fills the fields every push leaves unset from `option`, as by
+    /// [`PushOption::with_defaults`]. Its topic takes precedence over
+    /// [`with_topic`](Self::with_topic).
+    pub fn with_default_push_option(mut self, option: PushOption<'static>) -> Self {
+        self.default_push_option = option;
+        self
+    }
+
+    /// Retries failed [`push`](APNClient::push)es according to `policy`. Pushes are not retried
+    /// unless a policy is set. [`PushOption::retry_policy`] overrides it for a single push.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Fails a [`push`](APNClient::push) with [`TimeoutError`](APNClientError::TimeoutError)
+    /// that takes longer than `timeout`, retries included. [`PushOption::timeout`] overrides it
+    /// for a single push. Unlimited unless set.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fails a single request with an [`HTTPError`](APNClientError::HTTPError) if APNs has not
+    /// answered within `timeout`. Unlike [`with_timeout`](Self::with_timeout), this applies to
+    /// each attempt. Unlimited unless set.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Caps how fast every push method sends, making pushes wait once the limit is reached.
+    /// Unlimited unless set.
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Limits how many requests [`push_many`](APNClient::push_many) keeps in flight, adapting
+    /// the limit to how often APNs answers `429 TooManyRequests`. Unlimited unless set.
+    pub fn with_adaptive_concurrency(mut self, concurrency: AdaptiveConcurrency) -> Self {
+        self.concurrency = Some(concurrency);
+        self
+    }
+
+    /// Sets `TCP_NODELAY` on the connection, `true` by default.
+    ///
+    /// Pushes are small and latency sensitive, so they should not wait for Nagle's algorithm to
+    /// coalesce them with later writes.
+    pub fn with_tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.tcp_nodelay = nodelay;
+        self
+    }
+
+    /// Sets the TCP keepalive interval, or disables keepalive with `None`.
+    ///
+    /// APNs expects one long-lived connection per provider. The default of 60 seconds keeps an
+    /// idle connection from being silently dropped by NATs and load balancers in between.
+    pub fn with_tcp_keepalive(mut self, keepalive: Option<Duration>) -> Self {
+        self.tcp_keepalive = keepalive;
+        self
+    }
+
+    /// Caps how many idle connections to APNs the pool keeps open between pushes. Unlimited by
+    /// default.
+    ///
+    /// This does not limit how many connections are opened: pushes already share one HTTP/2
+    /// connection, and [`with_connections`](Self::with_connections) opens more. `0` closes a
+    /// connection as soon as it is idle, so the next push pays for another TLS handshake.
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Spreads pushes round-robin over `connections` separate connections to APNs instead of
+    /// one, raising the ceiling of concurrent pushes to that many times the stream limit of a
+    /// connection. `0` is treated as `1`, the default.
+    ///
+    /// Each connection costs a TLS handshake and is kept open, and APNs may treat many
+    /// connections from one provider as abuse, so only raise this when pushes queue for streams.
+    pub fn with_connections(mut self, connections: usize) -> Self {
+        self.connections = connections.max(1);
+        self
+    }
+
+    /// Retries a push on the other APNs port, 2197 for 443 and vice versa, when the connection
+    /// to the endpoint's port is refused, for networks blocking one of them. Later pushes stay
+    /// on the port that worked. Off by default, and ignored for endpoints on other ports.
+    pub fn with_port_fallback(mut self, fallback: bool) -> Self {
+        self.port_fallback = fallback;
+        self
+    }
+
+    /// Rejects pushes whose priority is not allowed for their push type with
+    /// [`PriorityNotAllowedError`](PushOptionError::PriorityNotAllowedError), rather than
+    /// letting APNs throttle them. Off by default.
+    pub fn with_strict_priority(mut self, strict: bool) -> Self {
+        self.strict_priority = strict;
+        self
+    }
+
+    /// Signs a provider token when the [`APNClient`] is built, so a key that loads but can not
+    /// sign fails there rather than on the first push. Off by default.
+    ///
+    /// The key id can not be checked locally; a mismatch only shows up as an
+    /// `InvalidProviderToken` response, which is logged with a hint.
+    pub fn with_verify_on_build(mut self, verify: bool) -> Self {
+        self.verify_on_build = verify;
+        self
+    }
+
+    /// Connects to `addr` instead of whatever the endpoint's host name resolves to.
+    ///
+    /// The host name is still sent as TLS SNI and `:authority`, and the certificate is still
+    /// verified against it, so `addr` must be an APNs gateway serving Apple's certificate. The
+    /// port of `addr` is ignored in favour of the endpoint's.
+    pub fn with_resolve(mut self, addr: SocketAddr) -> Self {
+        self.resolve = Some(addr);
+        self
+    }
+
+    fn parse_proxy(url: &str) -> Result<reqwest::Proxy, APNClientError> {
+        reqwest::Proxy::all(url).map_err(|_| InitializeError {
+            msg: format!("Unable to parse proxy url {}", url),
         })
     }
 }
 
+/// The outcome of [`APNClient::push_status_only`], with the response body left unread.
+pub struct PushStatus {
+    pub response: APNResponse,
+    pub status: ApnStatus,
+    body: reqwest::Response,
+}
+
+impl PushStatus {
+    pub fn is_success(&self) -> bool {
+        self.status.is_success()
+    }
+
+    /// Reads the error body, or returns `None` without reading anything if the push succeeded.
+    pub async fn error(self) -> Result<Option<APNErrorResponse>, APNClientError> {
+        if self.is_success() {
+            return Ok(None);
+        }
+        match self.body.json::<APNErrorResponse>().await {
+            Ok(error) => Ok(Some(error)),
+            Err(_) => Err(InvalidResponseError {
+                response: Some(Box::new(self.response)),
+            }),
+        }
+    }
+}
+
+/// The outcome of [`APNClient::health_check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Health {
+    /// APNs accepted the provider token and only rejected the device token.
+    Ok,
+    /// APNs rejected the provider token for `reason`.
+    AuthFailed(ApnReason),
+    /// APNs answered with a reason that says nothing about the provider token.
+    Unknown(ApnReason),
+}
+
+impl Health {
+    fn from_reason(reason: ApnReason) -> Self {
+        match reason {
+            ApnReason::BadDeviceToken | ApnReason::DeviceTokenNotForTopic => Self::Ok,
+            ApnReason::ExpiredProviderToken
+            | ApnReason::InvalidProviderToken
+            | ApnReason::MissingProviderToken
+            | ApnReason::UnrelatedKeyIdInToken
+            | ApnReason::Forbidden
+            | ApnReason::BadCertificate
+            | ApnReason::BadCertificateEnvironment => Self::AuthFailed(reason),
+            reason => Self::Unknown(reason),
+        }
+    }
+}
+
+pub struct PushRequest<'a> {
+    pub payload: &'a Payload,
+    pub device_token: &'a str,
+    pub option: PushOption<'a>,
+    /// Dispatches this request ahead of non-urgent ones in [`APNClient::push_many`].
+    ///
+    /// `reqwest` does not expose HTTP/2 stream priorities (which RFC 9113 deprecated), so
+    /// urgency only affects the order in which requests are written to the connection.
+    pub urgent: bool,
+}
+
+/// A client for the APNs provider API.
+///
+/// The client does not hold on to a tokio runtime: it can be created anywhere, and connections
+/// are opened lazily by whichever runtime drives the first push. That runtime keeps driving the
+/// connection afterwards, so in a multi-runtime application push from (or `Handle::spawn` onto)
+/// one long-lived runtime. Shutting down the runtime that opened a connection while the client
+/// is still in use elsewhere fails later pushes with a `dispatch task is gone` error.
+///
+/// The client is `Send + Sync`, so it can be moved into tasks or shared behind a lock.
 pub struct APNClient {
     config: APNClientConfig,
     token: Option<String>,
     signed_time: SystemTime,
-    http_client: reqwest::Client,
+    sign_duration: Option<Duration>,
+    http_clients: Vec<reqwest::Client>,
+    next_client: AtomicUsize,
+    fallback_endpoint: Option<String>,
+    on_fallback: AtomicBool,
+    http_version: OnceLock<Version>,
+    concurrency: Option<Mutex<AdaptiveConcurrency>>,
+    rate_limiter: Option<RateLimiter>,
 }
 
 impl APNClient {
     pub fn new(config: APNClientConfig) -> Result<Self, APNClientError> {
-        Ok(Self {
-            config,
+        let http_clients = (0..config.connections)
+            .map(|_| Self::http_client(&config))
+            .collect::<Result<_, _>>()?;
+        let mut client = Self {
+            concurrency: config.concurrency.clone().map(Mutex::new),
+            rate_limiter: config.rate_limit.map(RateLimiter::new),
             token: None,
-            signed_time: SystemTime::now(),
-            http_client: reqwest::Client::builder()
-                .use_rustls_tls()
-                .build()
-                .map_err(|_| InitializeError {
-                    msg: "Unable to initialize http client".to_string(),
-                })?,
+            signed_time: UNIX_EPOCH,
+            sign_duration: None,
+            http_version: OnceLock::new(),
+            http_clients,
+            next_client: AtomicUsize::new(0),
+            fallback_endpoint: config
+                .port_fallback
+                .then(|| alternate_endpoint(&config.endpoint, config.fallback_ports))
+                .flatten(),
+            on_fallback: AtomicBool::new(false),
+            config,
+        };
+        if client.config.verify_on_build {
+            client.sign()?;
+        }
+        Ok(client)
+    }
+
+    fn http_client(config: &APNClientConfig) -> Result<reqwest::Client, APNClientError> {
+        let mut builder = reqwest::Client::builder()
+            .use_rustls_tls()
+            .tcp_nodelay(config.tcp_nodelay)
+            .tcp_keepalive(config.tcp_keepalive)
+            .dns_resolver(Arc::new(DnsResolver {
+                inner: config.resolver.clone(),
+            }))
+            .connector_layer(ConnectLayer {
+                metrics: config.metrics.clone(),
+            });
+        if config.endpoint.starts_with("http://") {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(proxy) = config.proxy.clone() {
+            builder = builder.proxy(proxy);
+        }
+        if let Some(timeout) = config.request_timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(max) = config.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(addr) = config.resolve {
+            let url = reqwest::Url::parse(&config.endpoint).ok();
+            let host = url.as_ref().and_then(|url| url.host_str());
+            let host = host.ok_or_else(|| InitializeError {
+                msg: format!("Unable to parse endpoint {}", config.endpoint),
+            })?;
+            builder = builder.resolve(host, addr);
+        }
+        builder.build().map_err(|_| InitializeError {
+            msg: "Unable to initialize http client".to_string(),
         })
     }
 
-    fn sign(&mut self) -> Result<String, APNClientError> {
-        if let Some(token) = &self.token {
-            let now = SystemTime::now();
-            let duration = now
+    /// The current [adaptive concurrency](APNClientConfig::with_adaptive_concurrency) limit, or
+    /// `None` if concurrency is unlimited.
+    pub fn concurrency_limit(&self) -> Option<usize> {
+        let concurrency = self.concurrency.as_ref()?;
+        Some(concurrency.lock().unwrap().limit())
+    }
+
+    fn observe(&self, result: &Result<APNResponse, APNClientError>) {
+        let Some(concurrency) = &self.concurrency else {
+            return;
+        };
+        let mut concurrency = concurrency.lock().unwrap();
+        match result {
+            Ok(_) => concurrency.on_success(),
+            Err(APNError {
+                status: ApnStatus::TooManyRequests,
+                ..
+            }) => concurrency.on_throttled(),
+            Err(_) => {}
+        }
+    }
+
+    /// The HTTP version negotiated for the first response, or `None` before any push.
+    ///
+    /// APNs only speaks HTTP/2, so anything else means a proxy in between downgraded the
+    /// connection, which is also logged as a warning.
+    pub fn http_version(&self) -> Option<Version> {
+        self.http_version.get().copied()
+    }
+
+    /// How long ago the cached token was signed, or `None` before the first push.
+    pub fn token_age(&self) -> Option<Duration> {
+        self.token.as_ref()?;
+        Some(
+            self.config
+                .clock
+                .now()
                 .duration_since(self.signed_time)
-                .context(SystemTimeSnafu)?;
-            if duration < Duration::from_secs(60 * 20) {
-                return Ok(token.clone());
-            }
+                .unwrap_or_default(),
+        )
+    }
+
+    /// The [`Clock`] configured with [`APNClientConfig::with_clock`].
+    pub fn clock(&self) -> &dyn Clock {
+        self.config.clock.as_ref()
+    }
+
+    /// How long the cached token stays valid, or `None` before the first push.
+    ///
+    /// The token is re-signed once this drops to the
+    /// [refresh threshold](APNClientConfig::with_token_refresh_threshold).
+    pub fn token_expires_in(&self) -> Option<Duration> {
+        self.token.as_ref()?;
+        Some(
+            (self.signed_time + TOKEN_LIFETIME)
+                .duration_since(self.config.clock.now())
+                .unwrap_or_default(),
+        )
+    }
+
+    /// How long signing the most recent token took, or `None` before the first push.
+    pub fn last_sign_duration(&self) -> Option<Duration> {
+        self.sign_duration
+    }
+
+    /// Signs a new token right away, e.g. while idle, instead of on the next push.
+    pub fn refresh_token(&mut self) -> Result<(), APNClientError> {
+        self.sign_new(self.config.clock.now())?;
+        Ok(())
+    }
+
+    fn sign(&mut self) -> Result<String, APNClientError> {
+        let now = self.config.clock.now();
+        if let Some(token) = &self.token
+            && let Ok(remaining) = (self.signed_time + TOKEN_LIFETIME).duration_since(now)
+            && remaining > self.config.token_refresh_threshold
+        {
+            return Ok(token.clone());
         }
+        self.sign_new(now)
+    }
 
+    fn sign_new(&mut self, now: SystemTime) -> Result<String, APNClientError> {
         let mut header = Header::new(Algorithm::ES256);
         header.kid = Some(self.config.key_id.clone());
-        header.typ = None;
+        header.typ = self.config.jwt_typ.clone();
+        let issued_at = now.duration_since(UNIX_EPOCH).context(SystemTimeSnafu)?;
         let claims = APNTokenClaims {
             issuer_team_id: self.config.team_id.clone(),
-            issued_at: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .context(SystemTimeSnafu)?
-                .as_secs(),
+            issued_at: issued_at.as_secs(),
+            expires_at: (issued_at + TOKEN_LIFETIME).as_secs(),
         };
+        let started = Instant::now();
         let token = encode(&header, &claims, &self.config.key).map_err(|_| SignError {
             msg: "Unable to sign token".to_string(),
         })?;
+        let elapsed = started.elapsed();
+        self.sign_duration = Some(elapsed);
+        self.config.metrics.token_signed(elapsed);
         self.token = Some(token.clone());
+        self.signed_time = now;
         Ok(token)
     }
 
+    /// Sends a plain alert with a title and body, using [`PushOption::alert`].
+    ///
+    /// Use [`push`](Self::push) for anything beyond that.
+    pub async fn send_notification(
+        &mut self,
+        device_token: &str,
+        topic: &str,
+        title: &str,
+        body: &str,
+    ) -> Result<APNResponse, APNClientError> {
+        let payload = Payload::from(Notification {
+            alert: Some(
+                Alert::full()
+                    .with_title(Title::Normal(title.to_string()))
+                    .with_body(Body::Normal(body.to_string())),
+            ),
+            ..Notification::default()
+        });
+        self.push(&payload, device_token, PushOption::alert(topic))
+            .await
+    }
+
     pub async fn push(
         &mut self,
         payload: &Payload,
         device_token: &str,
         option: PushOption<'_>,
     ) -> Result<APNResponse, APNClientError> {
-        let path = format!("{}/3/device/{}", &self.config.endpoint, device_token);
+        self.send_with_retry(PushBody::Payload(payload), device_token, option)
+            .await
+    }
+
+    /// Like [`push`](Self::push), but gives up with [`TimeoutError`](APNClientError::TimeoutError)
+    /// once `deadline` passes.
+    ///
+    /// On expiry the in-flight request is dropped, which resets its HTTP/2 stream while leaving
+    /// the shared connection open for later pushes.
+    pub async fn push_until(
+        &mut self,
+        deadline: Instant,
+        payload: &Payload,
+        device_token: &str,
+        option: PushOption<'_>,
+    ) -> Result<APNResponse, APNClientError> {
         let token = self.sign()?;
-        let req = self
-            .http_client
-            .post(path)
-            .bearer_auth(token)
-            .headers(option.try_into().map_err(|_| HeaderError)?)
-            .json(payload);
-        let res = req.send().await.context(HTTPSnafu)?;
-        let headers = res.headers();
-        let id = String::from(
-            headers
-                .get("apns-id")
-                .context(InvalidResponseSnafu)?
-                .to_str()
-                .context(ToStrSnafu)?,
-        );
-        let unique_id = match headers.get("apns-unique-id") {
-            None => None,
-            Some(value) => Some(value.to_str().context(ToStrSnafu)?.to_string()),
-        };
-        let apn_response = APNResponse { id, unique_id };
-        let status = res.status().as_u16();
-        match status {
-            200 => Ok(apn_response),
-            _ => {
-                let error_response = res
-                    .json::<APNErrorResponse>()
-                    .await
-                    .map_err(|_| InvalidResponseError)?;
-                Err(APNError {
-                    response: apn_response,
-                    status,
-                    error: error_response,
-                })
-            }
+        let push = self.send(&token, payload, device_token, option);
+        match tokio::time::timeout_at(deadline.into(), push).await {
+            Ok(result) => result,
+            Err(_) => TimeoutSnafu.fail(),
+        }
+    }
+
+    /// Like [`push`](Self::push), but gives up with
+    /// [`CancelledError`](APNClientError::CancelledError) as soon as `cancel` completes, such as
+    /// `CancellationToken::cancelled()`.
+    ///
+    /// A push cancelled before it is sent never reaches APNs. One cancelled in flight has its
+    /// HTTP/2 stream reset, though APNs may already have accepted it.
+    pub async fn push_with_cancel(
+        &mut self,
+        payload: &Payload,
+        device_token: &str,
+        option: PushOption<'_>,
+        cancel: impl Future<Output = ()>,
+    ) -> Result<APNResponse, APNClientError> {
+        let cancel = pin!(cancel);
+        let push = pin!(self.push(payload, device_token, option));
+        match select(cancel, push).await {
+            Either::Left(_) => CancelledSnafu.fail(),
+            Either::Right((result, _)) => result,
         }
     }
+
+    /// Pushes to every device subscribed to a broadcast channel, such as a Live Activity channel.
+    ///
+    /// The bundle id is taken from the option's topic, without any push type suffix. The
+    /// `apns-collapse-id`, if set, applies to the channel as a whole: a newer broadcast with the
+    /// same collapse id replaces a pending one for every subscriber, where a device push only
+    /// collapses notifications for that one device.
+    pub async fn push_broadcast(
+        &mut self,
+        payload: &Payload,
+        channel_id: &str,
+        option: PushOption<'_>,
+    ) -> Result<BroadcastResponse, APNClientError> {
+        let token = self.sign()?;
+        let option = self.resolve_option(option)?;
+        let path = format!("/4/broadcast/apps/{}", Topic::new(option.topic).base());
+        let mut headers = HeaderMap::try_from(option).context(InvalidOptionSnafu)?;
+        headers.remove(APNS_TOPIC);
+        headers.insert(
+            "apns-channel-id",
+            channel_id.parse().map_err(|_| HeaderError)?,
+        );
+        let res = self.post(&token, payload, &path, headers).await?;
+        Ok(self.finish(res).await?.into())
+    }
+
+    /// Starts a Live Activity and returns the `apns-unique-id` APNs assigned to it.
+    ///
+    /// Keep the returned id to correlate later updates with this start. A start answered without
+    /// one fails with [`MissingUniqueIdError`](APNClientError::MissingUniqueIdError).
+    pub async fn start_live_activity(
+        &mut self,
+        payload: &Payload,
+        device_token: &str,
+        option: PushOption<'_>,
+    ) -> Result<String, APNClientError> {
+        let response = self.push(payload, device_token, option).await?;
+        match response.kind {
+            APNResponseKind::Broadcast { unique_id } => Ok(unique_id),
+            APNResponseKind::Device => MissingUniqueIdSnafu { id: response.id }.fail(),
+        }
+    }
+
+    /// Pushes every request concurrently over the same connection, signing the token once.
+    ///
+    /// The batch is rejected up front if two requests carry the same caller-supplied `apns-id`.
+    /// Otherwise urgent requests are dispatched first, and each request gets its own result in
+    /// the order the requests were given.
+    pub async fn push_many(
+        &mut self,
+        requests: Vec<PushRequest<'_>>,
+    ) -> Result<Vec<Result<APNResponse, APNClientError>>, APNClientError> {
+        let ids = duplicate_ids(&requests);
+        if !ids.is_empty() {
+            return Err(DuplicateIdError { ids });
+        }
+
+        let token = self.sign()?;
+        let token = &token;
+        let client = &*self;
+        let mut pending = in_dispatch_order(requests).into_iter();
+        let mut in_flight = FuturesUnordered::new();
+        let mut results = Vec::with_capacity(pending.len());
+        loop {
+            let limit = client.concurrency_limit().unwrap_or(usize::MAX);
+            while in_flight.len() < limit
+                && let Some((index, request)) = pending.next()
+            {
+                in_flight.push(async move {
+                    let result = client
+                        .send(token, request.payload, request.device_token, request.option)
+                        .await;
+                    (index, result)
+                });
+            }
+            let Some((index, result)) = in_flight.next().await else {
+                break;
+            };
+            client.observe(&result);
+            results.push((index, result));
+        }
+        results.sort_by_key(|(index, _)| *index);
+        Ok(results.into_iter().map(|(_, result)| result).collect())
+    }
+
+    /// Like [`push`](Self::push), but returns as soon as the response headers arrive.
+    ///
+    /// Reading and parsing the error body is deferred to [`PushStatus::error`], which callers
+    /// that only act on the status code can skip entirely.
+    pub async fn push_status_only(
+        &mut self,
+        payload: &Payload,
+        device_token: &str,
+        option: PushOption<'_>,
+    ) -> Result<PushStatus, APNClientError> {
+        let token = self.sign()?;
+        let res = self
+            .send_request(&token, payload, device_token, option)
+            .await?;
+        Ok(PushStatus {
+            response: APNResponse::try_from(res.headers())?,
+            status: res.status().as_u16().into(),
+            body: res,
+        })
+    }
+
+    /// Like [`push`](Self::push), but sends a payload already serialized with
+    /// [`Payload::to_bytes`], so pushing one payload to many devices serializes it only once.
+    ///
+    /// The body is sent as is: it is neither validated nor passed to the
+    /// [collapse strategy](APNClientConfig::with_collapse_strategy).
+    pub async fn push_bytes(
+        &mut self,
+        body: Bytes,
+        device_token: &str,
+        option: PushOption<'_>,
+    ) -> Result<APNResponse, APNClientError> {
+        self.send_with_retry(PushBody::Bytes(body), device_token, option)
+            .await
+    }
+
+    /// Sends a device push, retrying it by the push's or the client's retry policy, all within
+    /// the push's or the client's timeout.
+    async fn send_with_retry(
+        &mut self,
+        body: PushBody<'_>,
+        device_token: &str,
+        option: PushOption<'_>,
+    ) -> Result<APNResponse, APNClientError> {
+        let option = option.with_defaults(self.config.default_push_option);
+        let client_policy = self.config.retry_policy.clone();
+        let policy = option.retry_policy.or(client_policy.as_deref());
+        let timeout = option.timeout.or(self.config.timeout);
+        // Every attempt carries the same apns-id, so they can be told apart from other pushes in
+        // logs. APNs does not deduplicate by it. Without the `uuid` feature, APNs assigns one.
+        #[cfg(feature = "uuid")]
+        let id = (!option.has_id() && policy.is_some()).then(generate_apns_id);
+        #[cfg(not(feature = "uuid"))]
+        let id: Option<String> = None;
+        let option = PushOption {
+            id: option.id.or(id.as_deref()),
+            ..option
+        };
+        let push = async {
+            let mut attempt = 1;
+            loop {
+                let token = self.sign()?;
+                let result = match &body {
+                    PushBody::Payload(payload) => {
+                        self.send(&token, payload, device_token, option).await
+                    }
+                    PushBody::Bytes(bytes) => {
+                        let path = format!("{}{}", DEVICE_PATH, device_token);
+                        let headers = self.request_headers(option)?;
+                        let sent = self
+                            .post_bytes(&token, bytes.clone(), &path, headers, false)
+                            .await;
+                        match sent {
+                            Ok(res) => self.finish(res).await,
+                            Err(error) => Err(error),
+                        }
+                    }
+                };
+                let delay = match (&result, policy) {
+                    (Err(error), Some(policy)) => policy.next_delay(attempt, error),
+                    _ => None,
+                };
+                match delay {
+                    Some(delay) => tokio::time::sleep(delay).await,
+                    None => return result,
+                }
+                attempt += 1;
+            }
+        };
+        within(timeout, push).await
+    }
+
+    /// Checks connectivity and authentication without notifying anyone.
+    ///
+    /// APNs has no health endpoint, so this pushes a silent notification to a device token that
+    /// can not exist, for the [default topic](APNClientConfig::with_topic). APNs rejecting the
+    /// token means the connection and provider token work. Failing to reach APNs at all is an
+    /// error. Nothing is retried.
+    ///
+    /// Without a default topic, this fails with
+    /// [`MissingConfigError`](APNClientError::MissingConfigError) before anything is sent.
+    pub async fn health_check(&mut self) -> Result<Health, APNClientError> {
+        let option = PushOption::background("").with_defaults(self.config.default_push_option);
+        ensure!(
+            !option.topic.is_empty()
+                || self
+                    .config
+                    .topic
+                    .as_deref()
+                    .is_some_and(|topic| !topic.is_empty()),
+            MissingConfigSnafu { field: "topic" }
+        );
+        let token = self.sign()?;
+        let payload = Payload::from(Notification::silent());
+        match self
+            .send(&token, &payload, HEALTH_CHECK_TOKEN, option)
+            .await
+        {
+            Ok(_) => Ok(Health::Ok),
+            Err(error) => match error.reason() {
+                Some(reason) => Ok(Health::from_reason(reason)),
+                None => Err(error),
+            },
+        }
+    }
+
+    /// Like [`push`](Self::push), but returns the raw response for inspecting anything the
+    /// typed API does not model, such as headers APNs adds in the future.
+    ///
+    /// The status is not checked and nothing is retried. Reading the body, if needed, is up to
+    /// the caller.
+    pub async fn push_raw_response(
+        &mut self,
+        payload: &Payload,
+        device_token: &str,
+        option: PushOption<'_>,
+    ) -> Result<reqwest::Response, APNClientError> {
+        let token = self.sign()?;
+        self.send_request(&token, payload, device_token, option)
+            .await
+    }
+
+    async fn send_request(
+        &self,
+        token: &str,
+        payload: &Payload,
+        device_token: &str,
+        option: PushOption<'_>,
+    ) -> Result<reqwest::Response, APNClientError> {
+        let path = format!("{}{}", DEVICE_PATH, device_token);
+        let headers = self.request_headers(option)?;
+        self.post(token, payload, &path, headers).await
+    }
+
+    fn request_headers(&self, option: PushOption<'_>) -> Result<HeaderMap, APNClientError> {
+        HeaderMap::try_from(self.resolve_option(option)?).context(InvalidOptionSnafu)
+    }
+
+    /// Fills unset fields from the default option, then falls back to the configured topic if
+    /// the topic is still empty, and checks the topic against the allow-list.
+    fn resolve_option<'a>(
+        &'a self,
+        option: PushOption<'a>,
+    ) -> Result<PushOption<'a>, APNClientError> {
+        let option = option.with_defaults(self.config.default_push_option);
+        let option = match &self.config.topic {
+            Some(topic) if option.topic.is_empty() => PushOption { topic, ..option },
+            _ => option,
+        };
+        if self.config.strict_priority {
+            option.validate().context(InvalidOptionSnafu)?;
+        }
+        if let Some(allowed) = &self.config.allowed_topics {
+            let topic = Topic::new(option.topic);
+            ensure!(
+                allowed.contains(topic.as_str()) || allowed.contains(topic.base()),
+                TopicNotAllowedSnafu {
+                    topic: topic.as_str()
+                }
+            );
+        }
+        Ok(option)
+    }
+
+    async fn post(
+        &self,
+        token: &str,
+        payload: &Payload,
+        path: &str,
+        mut headers: HeaderMap,
+    ) -> Result<reqwest::Response, APNClientError> {
+        payload.validate().context(InvalidPayloadSnafu)?;
+        let mut collapse_derived = false;
+        if !headers.contains_key(APNS_COLLAPSE_ID)
+            && let Some(id) = self.config.collapse_strategy.collapse_id(payload)
+        {
+            let id = truncate_collapse_id(id);
+            headers.insert(APNS_COLLAPSE_ID, id.parse().map_err(|_| HeaderError)?);
+            collapse_derived = true;
+        }
+        let body = payload.to_bytes().context(InvalidPayloadSnafu)?;
+        self.post_bytes(token, body, path, headers, collapse_derived)
+            .await
+    }
+
+    /// Posts `body`; `collapse_derived` tells whether the collapse id came from the collapse
+    /// strategy rather than the push option.
+    async fn post_bytes(
+        &self,
+        token: &str,
+        body: Bytes,
+        path: &str,
+        headers: HeaderMap,
+        collapse_derived: bool,
+    ) -> Result<reqwest::Response, APNClientError> {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire(body.len()).await;
+        }
+        let sent = SentHeaders {
+            id: headers.get(APNS_ID).cloned(),
+            collapse_id: headers.get(APNS_COLLAPSE_ID).cloned(),
+            collapse_derived,
+            push_type: headers.get(APNS_PUSH_TYPE).cloned(),
+            topic: headers.get(APNS_TOPIC).cloned(),
+            started: Instant::now(),
+        };
+        // Round-robin over the connections; with a single one this always picks it.
+        let next = self.next_client.fetch_add(1, Ordering::Relaxed);
+        let http_client = &self.http_clients[next % self.http_clients.len()];
+        let on_fallback = self.on_fallback.load(Ordering::Relaxed);
+        let endpoint = match &self.fallback_endpoint {
+            Some(fallback) if on_fallback => fallback,
+            _ => &self.config.endpoint,
+        };
+        let req = http_client
+            .post(format!("{}{}", endpoint, path))
+            .bearer_auth(token)
+            .headers(headers.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        let mut res = match (req.send().await, &self.fallback_endpoint) {
+            (Err(error), Some(fallback))
+                if TransportFailure::classify(&error) == TransportFailure::Connect =>
+            {
+                let other = if on_fallback {
+                    &self.config.endpoint
+                } else {
+                    fallback
+                };
+                tracing::warn!(%endpoint, %other, "Unable to connect to APNs, trying the other port");
+                if let Some(limiter) = &self.rate_limiter {
+                    limiter.acquire(body.len()).await;
+                }
+                let req = http_client
+                    .post(format!("{}{}", other, path))
+                    .bearer_auth(token)
+                    .headers(headers)
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body);
+                let res = req.send().await.context(HTTPSnafu)?;
+                self.on_fallback.store(!on_fallback, Ordering::Relaxed);
+                res
+            }
+            (result, _) => result.context(HTTPSnafu)?,
+        };
+        res.extensions_mut().insert(sent);
+        self.http_version.get_or_init(|| {
+            let version = res.version();
+            if version != Version::HTTP_2 {
+                tracing::warn!(
+                    ?version,
+                    "APNs connection is not using HTTP/2; check for a proxy downgrading it"
+                );
+            }
+            version
+        });
+        Ok(res)
+    }
+
+    async fn send(
+        &self,
+        token: &str,
+        payload: &Payload,
+        device_token: &str,
+        option: PushOption<'_>,
+    ) -> Result<APNResponse, APNClientError> {
+        let res = self
+            .send_request(token, payload, device_token, option)
+            .await?;
+        self.finish(res).await
+    }
+
+    /// Reads the response, reporting it as a [`PushEvent`] to the metrics.
+    async fn finish(&self, res: reqwest::Response) -> Result<APNResponse, APNClientError> {
+        let sent = res.extensions().get::<SentHeaders>().cloned();
+        let status = res.status().as_u16();
+        let result = read_response(res).await;
+        if let Some(sent) = sent {
+            self.config
+                .metrics
+                .push_completed(&sent.event(status, &result));
+        }
+        self.diagnose(result)
+    }
+
+    /// Logs a hint for errors whose usual cause is in the client configuration.
+    fn diagnose(
+        &self,
+        result: Result<APNResponse, APNClientError>,
+    ) -> Result<APNResponse, APNClientError> {
+        if let Err(APNError { error, .. }) = &result
+            && error.kind() == ApnReason::InvalidProviderToken
+        {
+            tracing::warn!(
+                key_id = %self.config.key_id,
+                team_id = %self.config.team_id,
+                "APNs rejected the provider token; check that the key id belongs to the .p8 key \
+                 and the key to the team"
+            );
+        }
+        result
+    }
+}
+
+/// The body of a device push, serialized on every attempt or already serialized.
+enum PushBody<'a> {
+    Payload(&'a Payload),
+    Bytes(Bytes),
+}
+
+/// A random version 4 UUID in the canonical form APNs expects for `apns-id`.
+#[cfg(feature = "uuid")]
+fn generate_apns_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// The endpoint on the other one of `ports`, if `endpoint` is on one of them.
+fn alternate_endpoint(endpoint: &str, [first, second]: [u16; 2]) -> Option<String> {
+    let mut url = reqwest::Url::parse(endpoint).ok()?;
+    let port = match url.port_or_known_default()? {
+        port if port == first => second,
+        port if port == second => first,
+        _ => return None,
+    };
+    url.set_port(Some(port)).ok()?;
+    Some(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// Runs `push`, failing with [`TimeoutError`](APNClientError::TimeoutError) if it takes longer
+/// than `timeout`.
+async fn within<T>(
+    timeout: Option<Duration>,
+    push: impl Future<Output = Result<T, APNClientError>>,
+) -> Result<T, APNClientError> {
+    match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, push).await {
+            Ok(result) => result,
+            Err(_) => TimeoutSnafu.fail(),
+        },
+        None => push.await,
+    }
+}
+
+async fn read_response(res: reqwest::Response) -> Result<APNResponse, APNClientError> {
+    let status = ApnStatus::from(res.status().as_u16());
+    let sent = res.extensions().get::<SentHeaders>().cloned();
+    let elapsed = sent.as_ref().map(|sent| sent.started.elapsed());
+    let parse = |headers: &HeaderMap| {
+        APNResponse::try_from(headers).map(|response| APNResponse {
+            elapsed: elapsed.unwrap_or_default(),
+            ..response
+        })
+    };
+    if matches!(status, ApnStatus::NotFound | ApnStatus::MethodNotAllowed) {
+        let response = parse(res.headers()).ok().map(Box::new);
+        let path = redact_device_token(res.url().path());
+        let error = res.json::<APNErrorResponse>().await.ok().map(Box::new);
+        return Err(RequestPathError {
+            status,
+            path,
+            response,
+            error,
+        });
+    }
+    let apn_response = parse(res.headers())?;
+    match status {
+        ApnStatus::Success => Ok(apn_response),
+        _ => match res.json::<APNErrorResponse>().await {
+            Ok(error_response) => Err(APNError {
+                rejected: sent
+                    .and_then(|sent| sent.rejected(error_response.kind()))
+                    .map(Box::new),
+                response: Box::new(apn_response),
+                status,
+                error: Box::new(error_response),
+            }),
+            Err(_) => Err(InvalidResponseError {
+                response: Some(Box::new(apn_response)),
+            }),
+        },
+    }
+}
+
+/// Header values kept on the response to name the culprit when APNs rejects one of them, and
+/// to describe the push in a [`PushEvent`].
+#[derive(Clone)]
+struct SentHeaders {
+    id: Option<HeaderValue>,
+    collapse_id: Option<HeaderValue>,
+    collapse_derived: bool,
+    push_type: Option<HeaderValue>,
+    topic: Option<HeaderValue>,
+    started: Instant,
+}
+
+impl SentHeaders {
+    /// The header blamed by `reason`, if it is one that blames a header that was sent.
+    fn rejected(self, reason: ApnReason) -> Option<RejectedOption> {
+        let (field, value) = match reason {
+            ApnReason::BadCollapseId if self.collapse_derived => {
+                (RejectedField::CollapseStrategy, self.collapse_id?)
+            }
+            ApnReason::BadCollapseId => (RejectedField::CollapseId, self.collapse_id?),
+            ApnReason::BadMessageId => (RejectedField::Id, self.id?),
+            _ => return None,
+        };
+        Some(RejectedOption {
+            field,
+            value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        })
+    }
+
+    fn event(&self, status: u16, result: &Result<APNResponse, APNClientError>) -> PushEvent {
+        let text = |value: &Option<HeaderValue>| {
+            value
+                .as_ref()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        };
+        let response = match result {
+            Ok(response) => Some(response),
+            Err(APNError { response, .. })
+            | Err(InvalidResponseError {
+                response: Some(response),
+            })
+            | Err(RequestPathError {
+                response: Some(response),
+                ..
+            }) => Some(&**response),
+            Err(_) => None,
+        };
+        let reason = match result {
+            Err(APNError { error, .. })
+            | Err(RequestPathError {
+                error: Some(error), ..
+            }) => Some(error.reason.clone()),
+            Err(error) => error.reason().map(|reason| reason.to_string()),
+            Ok(_) => None,
+        };
+        PushEvent {
+            apns_id: response
+                .map(|response| response.id.clone())
+                .or_else(|| text(&self.id)),
+            status,
+            reason,
+            push_type: text(&self.push_type),
+            topic_redacted: text(&self.topic).map(|topic| Topic::new(topic).redacted()),
+            latency: self.started.elapsed(),
+        }
+    }
+}
+
+fn redact_device_token(path: &str) -> String {
+    match path.strip_prefix(DEVICE_PATH) {
+        Some(_) => format!("{}<redacted>", DEVICE_PATH),
+        None => path.to_string(),
+    }
+}
+
+fn in_dispatch_order(requests: Vec<PushRequest>) -> Vec<(usize, PushRequest)> {
+    let mut requests = requests.into_iter().enumerate().collect::<Vec<_>>();
+    requests.sort_by_key(|(_, request)| !request.urgent);
+    requests
+}
+
+fn duplicate_ids(requests: &[PushRequest]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    for id in requests.iter().filter_map(|request| request.option.id) {
+        if !seen.insert(id) && !duplicates.iter().any(|duplicate| duplicate == id) {
+            duplicates.push(id.to_string());
+        }
+    }
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::in_dispatch_order;
+    use crate::test_util::{MockResponse, MockServer, TEST_KEY_PEM};
+    use crate::{
+        APNClient, APNClientConfig, APNClientError, APNResponse, APNResponseKind,
+        AdaptiveConcurrency, ApnReason, ApnStatus, Clock, CollapseStrategy, ContentHash, Endpoint,
+        Health, KeySource, Metrics, Notification, Payload, PushEvent, PushOption, PushOptionError,
+        PushRequest, PushType, RateLimit, RejectedField, RetryPolicy, TransportFailure,
+    };
+    use jsonwebtoken::{DecodingKey, EncodingKey, Validation, decode, decode_header};
+    use reqwest::dns::{Name, Resolve, Resolving};
+    use reqwest::header::HeaderMap;
+    use serde::Deserialize;
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+    use tokio::net::TcpSocket;
+
+    const TEST_KEY_DER: &[u8] = &[
+        0x30, 0x81, 0x87, 0x02, 0x01, 0x00, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d,
+        0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x04, 0x6d, 0x30,
+        0x6b, 0x02, 0x01, 0x01, 0x04, 0x20, 0x1f, 0xf6, 0x10, 0x61, 0xbf, 0xb4, 0x3e, 0xfd, 0x7b,
+        0xbf, 0x6c, 0xa2, 0x8b, 0xe1, 0x11, 0xc2, 0xfb, 0xe2, 0x06, 0x20, 0x69, 0x8a, 0xa7, 0xef,
+        0x2b, 0x64, 0xdd, 0xc0, 0x6f, 0x2d, 0xbf, 0x5b, 0xa1, 0x44, 0x03, 0x42, 0x00, 0x04, 0x5b,
+        0x3c, 0x13, 0x03, 0x47, 0x93, 0x01, 0x27, 0x21, 0xf3, 0xc3, 0x42, 0x05, 0x86, 0xef, 0xe6,
+        0xf1, 0xf6, 0x10, 0x97, 0x01, 0x6e, 0x24, 0x45, 0xd4, 0xdd, 0x1c, 0x37, 0x91, 0x6b, 0x65,
+        0x6b, 0x68, 0x21, 0x1e, 0x38, 0x35, 0x62, 0xda, 0xf1, 0x4a, 0x67, 0xc2, 0xd6, 0xa1, 0xde,
+        0xdd, 0x24, 0x60, 0x7f, 0xd3, 0x5e, 0x07, 0x2d, 0x0d, 0xa7, 0x20, 0xf4, 0x26, 0x3a, 0x3f,
+        0x12, 0x18, 0x9b,
+    ];
+
+    #[test]
+    fn test_sign_with_pem_key() {
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap();
+        let mut client = APNClient::new(cfg).unwrap();
+        assert!(!client.sign().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sign_with_der_key() {
+        let cfg = APNClientConfig::new_from_der(
+            "TEAM_ID",
+            "KEY_ID",
+            TEST_KEY_DER,
+            Endpoint::development(),
+        )
+        .unwrap();
+        let mut client = APNClient::new(cfg).unwrap();
+        let token = client.sign().unwrap();
+        assert_eq!(3, token.split('.').count());
+    }
+
+    #[test]
+    fn test_verify_on_build() {
+        struct Unchecked;
+
+        impl KeySource for Unchecked {
+            fn load(&self) -> Result<EncodingKey, APNClientError> {
+                Ok(EncodingKey::from_ec_der(b"not a key"))
+            }
+        }
+
+        let cfg = || {
+            APNClientConfig::from_source("TEAM_ID", "KEY_ID", &Unchecked, Endpoint::development())
+                .unwrap()
+        };
+        assert!(APNClient::new(cfg()).is_ok());
+        let res = APNClient::new(cfg().with_verify_on_build(true));
+        assert!(matches!(res, Err(APNClientError::SignError { .. })));
+
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap()
+            .with_verify_on_build(true);
+        let client = APNClient::new(cfg).unwrap();
+        assert!(client.token_age().is_some());
+    }
+
+    #[test]
+    fn test_invalid_der_key() {
+        let res = APNClientConfig::new_from_der(
+            "TEAM_ID",
+            "KEY_ID",
+            &TEST_KEY_DER[..32],
+            Endpoint::development(),
+        );
+        assert!(matches!(res, Err(APNClientError::InvalidKeyError)));
+    }
+
+    #[test]
+    fn test_device_response() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "apns-id",
+            "EEEEEEEE-0000-0000-0000-000000000001".parse().unwrap(),
+        );
+        let response = APNResponse::try_from(&headers).unwrap();
+        assert_eq!("EEEEEEEE-0000-0000-0000-000000000001", response.id);
+        assert!(matches!(response.kind, APNResponseKind::Device));
+        assert!(!response.is_broadcast());
+        assert_eq!(None, response.unique_id());
+    }
+
+    #[test]
+    fn test_broadcast_response() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "apns-id",
+            "EEEEEEEE-0000-0000-0000-000000000002".parse().unwrap(),
+        );
+        headers.insert(
+            "apns-unique-id",
+            "a7c6b1f0-1234-4c1f-9f1a-000000000000".parse().unwrap(),
+        );
+        let response = APNResponse::try_from(&headers).unwrap();
+        assert!(response.is_broadcast());
+        assert_eq!(
+            Some("a7c6b1f0-1234-4c1f-9f1a-000000000000"),
+            response.unique_id()
+        );
+    }
+
+    #[test]
+    fn test_response_without_id() {
+        let headers = HeaderMap::new();
+        assert!(APNResponse::try_from(&headers).is_err());
+    }
+
+    struct TestClock(Mutex<SystemTime>);
+
+    impl TestClock {
+        fn advance(&self, duration: Duration) {
+            *self.0.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> SystemTime {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct Claims {
+        iat: u64,
+        exp: u64,
+    }
+
+    fn claims(token: &str) -> Claims {
+        let mut validation = Validation::default();
+        validation.insecure_disable_signature_validation();
+        validation.validate_exp = false;
+        decode::<Claims>(token, &DecodingKey::from_secret(&[]), &validation)
+            .unwrap()
+            .claims
+    }
+
+    #[test]
+    fn test_jwt_typ() {
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap();
+        let mut client = APNClient::new(cfg).unwrap();
+        assert_eq!(None, decode_header(&client.sign().unwrap()).unwrap().typ);
+
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap()
+            .with_jwt_typ(Some("JWT"));
+        let mut client = APNClient::new(cfg).unwrap();
+        let header = decode_header(&client.sign().unwrap()).unwrap();
+        assert_eq!(Some("JWT".to_string()), header.typ);
+        assert_eq!(Some("KEY_ID".to_string()), header.kid);
+    }
+
+    #[test]
+    fn test_token_refresh_boundary() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = Arc::new(TestClock(Mutex::new(start)));
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap()
+            .with_clock(clock.clone())
+            .with_token_refresh_threshold(Duration::from_secs(60 * 5));
+        let mut client = APNClient::new(cfg).unwrap();
+
+        let token = client.sign().unwrap();
+        let issued = claims(&token);
+        assert_eq!(1_700_000_000, issued.iat);
+        assert_eq!(1_700_000_000 + 60 * 60, issued.exp);
+
+        clock.advance(Duration::from_secs(60 * 55 - 1));
+        assert_eq!(token, client.sign().unwrap());
+
+        clock.advance(Duration::from_secs(1));
+        let refreshed = client.sign().unwrap();
+        assert_ne!(token, refreshed);
+        assert_eq!(1_700_000_000 + 60 * 55, claims(&refreshed).iat);
+    }
+
+    #[test]
+    fn test_token_age() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = Arc::new(TestClock(Mutex::new(start)));
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap()
+            .with_clock(clock.clone());
+        let mut client = APNClient::new(cfg).unwrap();
+        assert_eq!(None, client.token_age());
+        assert_eq!(None, client.token_expires_in());
+
+        client.sign().unwrap();
+        clock.advance(Duration::from_secs(60 * 15));
+        assert_eq!(Some(Duration::from_secs(60 * 15)), client.token_age());
+        assert_eq!(
+            Some(Duration::from_secs(60 * 45)),
+            client.token_expires_in()
+        );
+
+        client.refresh_token().unwrap();
+        assert_eq!(Some(Duration::ZERO), client.token_age());
+        assert_eq!(
+            Some(Duration::from_secs(60 * 60)),
+            client.token_expires_in()
+        );
+
+        clock.advance(Duration::from_secs(60 * 90));
+        assert_eq!(Some(Duration::ZERO), client.token_expires_in());
+    }
+
+    #[test]
+    fn test_sign_metrics() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<Duration>>);
+
+        impl Metrics for Recorder {
+            fn token_signed(&self, elapsed: Duration) {
+                self.0.lock().unwrap().push(elapsed);
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap()
+            .with_metrics(recorder.clone());
+        let mut client = APNClient::new(cfg).unwrap();
+        assert_eq!(None, client.last_sign_duration());
+
+        client.sign().unwrap();
+        client.sign().unwrap();
+        let recorded = recorder.0.lock().unwrap().clone();
+        assert_eq!(1, recorded.len());
+        assert_eq!(Some(recorded[0]), client.last_sign_duration());
+    }
+
+    #[tokio::test]
+    async fn test_connection_metrics() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<Duration>>);
+
+        impl Metrics for Recorder {
+            fn connection_opened(&self, elapsed: Duration) {
+                self.0.lock().unwrap().push(elapsed);
+            }
+        }
+
+        let server = MockServer::start().await;
+        let recorder = Arc::new(Recorder::default());
+        let cfg = server.config().unwrap().with_metrics(recorder.clone());
+        let mut client = APNClient::new(cfg).unwrap();
+        for _ in 0..3 {
+            client
+                .push(
+                    &Payload::default(),
+                    "device-token",
+                    PushOption::alert("com.example.app"),
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(1, recorder.0.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_connections() {
+        #[derive(Default)]
+        struct Counter(AtomicUsize);
+
+        impl Metrics for Counter {
+            fn connection_opened(&self, _: Duration) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let server = MockServer::start().await;
+        let counter = Arc::new(Counter::default());
+        let cfg = server
+            .config()
+            .unwrap()
+            .with_connections(3)
+            .with_metrics(counter.clone());
+        let mut client = APNClient::new(cfg).unwrap();
+        for _ in 0..6 {
+            client
+                .push(
+                    &Payload::default(),
+                    "device-token",
+                    PushOption::alert("com.example.app"),
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(3, counter.0.load(Ordering::Relaxed));
+        assert_eq!(6, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_push_events() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<PushEvent>>);
+
+        impl Metrics for Recorder {
+            fn push_completed(&self, event: &PushEvent) {
+                self.0.lock().unwrap().push(event.clone());
+            }
+        }
+
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::ok().with_header("apns-id", "id-1"));
+        server.enqueue(MockResponse::error(400, "BadDeviceToken").with_header("apns-id", "id-2"));
+        let recorder = Arc::new(Recorder::default());
+        let cfg = server.config().unwrap().with_metrics(recorder.clone());
+        let mut client = APNClient::new(cfg).unwrap();
+        for _ in 0..2 {
+            let _ = client
+                .push(
+                    &Payload::default(),
+                    "secret-device-token",
+                    PushOption::alert("com.example.app"),
+                )
+                .await;
+        }
+
+        let events = recorder.0.lock().unwrap().clone();
+        assert_eq!(2, events.len());
+        assert_eq!(Some("id-1"), events[0].apns_id.as_deref());
+        assert_eq!(200, events[0].status);
+        assert_eq!(None, events[0].reason);
+        assert_eq!(Some("alert"), events[0].push_type.as_deref());
+        assert_eq!(Some("com.<redacted>"), events[0].topic_redacted.as_deref());
+        assert_eq!(Some("id-2"), events[1].apns_id.as_deref());
+        assert_eq!(400, events[1].status);
+        assert_eq!(Some("BadDeviceToken"), events[1].reason.as_deref());
+        for event in events {
+            let json = serde_json::to_string(&event).unwrap();
+            assert!(!json.contains("secret-device-token"));
+            assert!(!json.contains("example"));
+            assert!(!json.to_lowercase().contains("bearer"));
+        }
+    }
+
+    #[test]
+    fn test_proxy_with_auth() {
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap()
+            .with_proxy_auth("http://proxy.example.com:3128", "user", "secret")
+            .unwrap();
+        assert!(cfg.proxy.is_some());
+        assert!(APNClient::new(cfg).is_ok());
+
+        let res = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap()
+            .with_proxy_auth("not a url", "user", "secret");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_client_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<APNClient>();
+        assert_send_sync::<APNClientConfig>();
+        assert_send_sync::<APNClientError>();
+    }
+
+    #[test]
+    fn test_tcp_options() {
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap();
+        assert!(cfg.tcp_nodelay);
+        assert_eq!(Some(Duration::from_secs(60)), cfg.tcp_keepalive);
+
+        let cfg = cfg.with_tcp_nodelay(false).with_tcp_keepalive(None);
+        assert!(!cfg.tcp_nodelay);
+        assert_eq!(None, cfg.tcp_keepalive);
+        assert!(APNClient::new(cfg).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_collapse_strategy() {
+        let server = MockServer::start().await;
+        let cfg = server.config().unwrap().with_collapse_strategy(ContentHash);
+        let mut client = APNClient::new(cfg).unwrap();
+        let payload = Payload::default();
+
+        client
+            .push(
+                &payload,
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap();
+        let expected = ContentHash.collapse_id(&payload).unwrap();
+        let request = server.last_request().unwrap();
+        assert_eq!(Some(expected.as_str()), request.header("apns-collapse-id"));
+
+        let option = PushOption {
+            collapse_id: Some("explicit"),
+            ..PushOption::alert("com.example.app")
+        };
+        client.push(&payload, "device-token", option).await.unwrap();
+        let request = server.last_request().unwrap();
+        assert_eq!(Some("explicit"), request.header("apns-collapse-id"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_override() {
+        let server = MockServer::start().await;
+        let endpoint = Endpoint::plaintext("api.push.invalid", server.addr().port());
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, endpoint)
+            .unwrap()
+            .with_resolve(server.addr());
+        let mut client = APNClient::new(cfg).unwrap();
+
+        client
+            .push(
+                &Payload::default(),
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(1, server.requests().len());
+    }
+
+    #[test]
+    fn test_alternate_endpoint() {
+        assert_eq!(
+            Some("https://api.push.apple.com:2197"),
+            super::alternate_endpoint(&String::from(Endpoint::production()), super::APNS_PORTS)
+                .as_deref()
+        );
+        assert_eq!(
+            Some("https://api.push.apple.com"),
+            super::alternate_endpoint(
+                &String::from(Endpoint::production_alter()),
+                super::APNS_PORTS
+            )
+            .as_deref()
+        );
+        assert_eq!(
+            None,
+            super::alternate_endpoint("http://127.0.0.1:8080", super::APNS_PORTS)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_port_fallback() {
+        // Nothing listens on the closed port, so the push falls back to the server.
+        let server = MockServer::start().await;
+        let (_socket, closed) = refusing_port();
+        let endpoint = Endpoint::plaintext("127.0.0.1", closed);
+        let mut cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, endpoint)
+            .unwrap()
+            .with_port_fallback(true);
+        cfg.fallback_ports = [closed, server.addr().port()];
+        let mut client = APNClient::new(cfg).unwrap();
+
+        for _ in 0..2 {
+            client
+                .push(
+                    &Payload::default(),
+                    "device-token",
+                    PushOption::alert("com.example.app"),
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(2, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_single_connection() {
+        #[derive(Default)]
+        struct Counter(AtomicUsize);
+
+        impl Metrics for Counter {
+            fn connection_opened(&self, _: Duration) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let server = MockServer::start().await;
+        let counter = Arc::new(Counter::default());
+        let cfg = server
+            .config()
+            .unwrap()
+            .with_pool_max_idle_per_host(1)
+            .with_metrics(counter.clone());
+        assert_eq!(Some(1), cfg.pool_max_idle_per_host);
+        let mut client = APNClient::new(cfg).unwrap();
+
+        for _ in 0..3 {
+            client
+                .push(
+                    &Payload::default(),
+                    "device-token",
+                    PushOption::alert("com.example.app"),
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(1, counter.0.load(Ordering::Relaxed));
+        assert_eq!(3, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_push_many_duplicate_ids() {
+        let cfg = APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, Endpoint::development())
+            .unwrap();
+        let mut client = APNClient::new(cfg).unwrap();
+        let payload = Payload::default();
+        let request = |device_token, id| PushRequest {
+            payload: &payload,
+            device_token,
+            option: PushOption {
+                id: Some(id),
+                ..PushOption::alert("com.example.app")
+            },
+            urgent: false,
+        };
+
+        let res = client
+            .push_many(vec![
+                request("token-a", "EEEEEEEE-0000-0000-0000-000000000001"),
+                request("token-b", "EEEEEEEE-0000-0000-0000-000000000002"),
+                request("token-c", "EEEEEEEE-0000-0000-0000-000000000001"),
+            ])
+            .await;
+        match res {
+            Err(APNClientError::DuplicateIdError { ids }) => {
+                assert_eq!(vec!["EEEEEEEE-0000-0000-0000-000000000001"], ids)
+            }
+            _ => panic!("expected a duplicate id error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let server = MockServer::start().await;
+        let cfg = server
+            .config()
+            .unwrap()
+            .with_rate_limit(RateLimit::requests_per_second(10));
+        let mut client = APNClient::new(cfg).unwrap();
+        let payload = Payload::default();
+
+        // A burst of 10 goes out at once, the 5 after it at 10 per second.
+        let start = Instant::now();
+        for _ in 0..15 {
+            client
+                .push(
+                    &payload,
+                    "device-token",
+                    PushOption::alert("com.example.app"),
+                )
+                .await
+                .unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(450));
+        assert_eq!(15, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_concurrency() {
+        let server = MockServer::start().await;
+        for _ in 0..3 {
+            server.enqueue(MockResponse::too_many_requests(1));
+        }
+        let config = server
+            .config()
+            .unwrap()
+            .with_adaptive_concurrency(AdaptiveConcurrency::new(1, 8));
+        let mut client = APNClient::new(config).unwrap();
+        assert_eq!(Some(8), client.concurrency_limit());
+        let payload = Payload::default();
+        let requests = (0..4)
+            .map(|_| PushRequest {
+                payload: &payload,
+                device_token: "device-token",
+                option: PushOption::alert("com.example.app"),
+                urgent: false,
+            })
+            .collect();
+
+        let results = client.push_many(requests).await.unwrap();
+        assert_eq!(3, results.iter().filter(|result| result.is_err()).count());
+        // Halved three times down to 1, possibly grown by one after the success.
+        assert!(client.concurrency_limit().unwrap() <= 2);
+    }
+
+    #[test]
+    fn test_urgent_requests_dispatched_first() {
+        let payload = Payload::default();
+        let request = |device_token, urgent| PushRequest {
+            payload: &payload,
+            device_token,
+            option: PushOption::alert("com.example.app"),
+            urgent,
+        };
+
+        let requests = vec![
+            request("bulk-a", false),
+            request("voip-a", true),
+            request("bulk-b", false),
+            request("voip-b", true),
+        ];
+        let order = in_dispatch_order(requests)
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 3, 0, 2], order);
+    }
+
+    #[tokio::test]
+    async fn test_push_to_mock_server() {
+        let server = MockServer::start().await;
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let payload = Payload::default();
+
+        let response = client
+            .push(
+                &payload,
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap();
+        assert!(!response.is_broadcast());
+
+        let request = server.last_request().unwrap();
+        assert_eq!("POST", request.method);
+        assert_eq!(Some("device-token"), request.device_token());
+        assert_eq!(Some("com.example.app"), request.header("apns-topic"));
+        assert_eq!(Some("alert"), request.header("apns-push-type"));
+        assert!(
+            request
+                .header("authorization")
+                .unwrap()
+                .starts_with("Bearer ")
+        );
+        assert_eq!(serde_json::json!({ "aps": {} }), request.json());
+        assert_eq!("00000000-0000-0000-0000-000000000001", response.id);
+    }
+
+    #[tokio::test]
+    async fn test_send_notification() {
+        let server = MockServer::start().await;
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+
+        client
+            .send_notification("device-token", "com.example.app", "Title", "Body")
+            .await
+            .unwrap();
+
+        let request = server.last_request().unwrap();
+        assert_eq!(Some("device-token"), request.device_token());
+        assert_eq!(Some("com.example.app"), request.header("apns-topic"));
+        assert_eq!(Some("alert"), request.header("apns-push-type"));
+        assert_eq!(Some("10"), request.header("apns-priority"));
+        assert_eq!(
+            serde_json::json!({ "aps": { "alert": { "title": "Title", "body": "Body" } } }),
+            request.json()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_start_live_activity() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::ok().with_header("apns-unique-id", "unique-1"));
+        server.enqueue(MockResponse::ok());
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let payload = Payload::default();
+        let option = || PushOption {
+            push_type: Some(PushType::LiveActivity),
+            topic: "com.example.app.push-type.liveactivity",
+            ..PushOption::default()
+        };
+
+        let unique_id = client
+            .start_live_activity(&payload, "push-to-start-token", option())
+            .await
+            .unwrap();
+        assert_eq!("unique-1", unique_id);
+
+        let error = client
+            .start_live_activity(&payload, "push-to-start-token", option())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, APNClientError::MissingUniqueIdError { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_default_topic() {
+        let server = MockServer::start().await;
+        let config = server.config().unwrap().with_topic("com.example.default");
+        let mut client = APNClient::new(config).unwrap();
+        let payload = Payload::default();
+
+        for topic in ["", "com.example.app"] {
+            client
+                .push(&payload, "device-token", PushOption::alert(topic))
+                .await
+                .unwrap();
+        }
+        let topics = server
+            .requests()
+            .iter()
+            .map(|request| request.header("apns-topic").unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["com.example.default", "com.example.app"], topics);
+    }
+
+    #[tokio::test]
+    async fn test_default_push_option() {
+        let server = MockServer::start().await;
+        let default = PushOption {
+            expiration: Some(1_700_000_000),
+            collapse_id: Some("default"),
+            ..PushOption::background("com.example.app")
+        };
+        let config = server.config().unwrap().with_default_push_option(default);
+        let mut client = APNClient::new(config).unwrap();
+        let payload = Payload::default();
+
+        client
+            .push(&payload, "device-token", PushOption::default())
+            .await
+            .unwrap();
+        let request = server.last_request().unwrap();
+        assert_eq!(Some("background"), request.header("apns-push-type"));
+        assert_eq!(Some("5"), request.header("apns-priority"));
+        assert_eq!(Some("1700000000"), request.header("apns-expiration"));
+        assert_eq!(Some("com.example.app"), request.header("apns-topic"));
+
+        let option = PushOption {
+            collapse_id: Some("override"),
+            ..PushOption::alert("")
+        };
+        client.push(&payload, "device-token", option).await.unwrap();
+        let request = server.last_request().unwrap();
+        assert_eq!(Some("alert"), request.header("apns-push-type"));
+        assert_eq!(Some("10"), request.header("apns-priority"));
+        assert_eq!(Some("override"), request.header("apns-collapse-id"));
+        assert_eq!(Some("1700000000"), request.header("apns-expiration"));
+        assert_eq!(Some("com.example.app"), request.header("apns-topic"));
+    }
+
+    #[tokio::test]
+    async fn test_strict_priority() {
+        let server = MockServer::start().await;
+        let config = server.config().unwrap().with_strict_priority(true);
+        let mut client = APNClient::new(config).unwrap();
+        let option = PushOption {
+            priority: Some(10),
+            ..PushOption::background("com.example.app")
+        };
+
+        let error = client
+            .push(&Payload::default(), "device-token", option)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            APNClientError::InvalidOptionError {
+                source: PushOptionError::PriorityNotAllowedError { .. }
+            }
+        ));
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_allowed_topics() {
+        let server = MockServer::start().await;
+        let config = server
+            .config()
+            .unwrap()
+            .with_allowed_topics(["com.example.app"]);
+        let mut client = APNClient::new(config).unwrap();
+        let payload = Payload::default();
+
+        for topic in ["com.example.app", "com.example.app.voip"] {
+            client
+                .push(&payload, "device-token", PushOption::voip(topic))
+                .await
+                .unwrap();
+        }
+        let error = client
+            .push(
+                &payload,
+                "device-token",
+                PushOption::alert("com.example.other"),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            APNClientError::TopicNotAllowedError { topic } if topic == "com.example.other"
+        ));
+        assert_eq!(2, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_http_version() {
+        let server = MockServer::start().await;
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        assert_eq!(None, client.http_version());
+
+        client
+            .push(
+                &Payload::default(),
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(Some(reqwest::Version::HTTP_2), client.http_version());
+    }
+
+    #[tokio::test]
+    async fn test_push_with_cancel() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::ok().with_delay(Duration::from_secs(30)));
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let payload = Payload::default();
+        let option = PushOption::alert("com.example.app");
+
+        let error = client
+            .push_with_cancel(&payload, "device-token", option, std::future::ready(()))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, APNClientError::CancelledError));
+        assert!(server.requests().is_empty());
+
+        let started = Instant::now();
+        let cancel = tokio::time::sleep(Duration::from_millis(200));
+        let error = client
+            .push_with_cancel(&payload, "device-token", option, cancel)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, APNClientError::CancelledError));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let cancel = std::future::pending();
+        client
+            .push_with_cancel(&payload, "device-token", option, cancel)
+            .await
+            .unwrap();
+        assert_eq!(2, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_push_until_deadline() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::ok().with_delay(Duration::from_secs(30)));
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let payload = Payload::default();
+
+        let deadline = Instant::now() + Duration::from_millis(200);
+        let error = client
+            .push_until(
+                deadline,
+                &payload,
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, APNClientError::TimeoutError));
+        assert!(Instant::now() < deadline + Duration::from_secs(5));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        client
+            .push_until(
+                deadline,
+                &payload,
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(2, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy() {
+        let server = MockServer::start().await;
+        for _ in 0..3 {
+            server.enqueue(MockResponse::error(500, "InternalServerError"));
+        }
+        server.enqueue(MockResponse::error(429, "TooManyRequests"));
+        let policy = RetryPolicy::new(4)
+            .with_backoff(Duration::ZERO, Duration::ZERO)
+            .with_reason_max_attempts(ApnReason::InternalServerError, 2);
+        let config = server.config().unwrap().with_retry_policy(policy);
+        let mut client = APNClient::new(config).unwrap();
+        let payload = Payload::default();
+
+        let error = client
+            .push(
+                &payload,
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.is_retryable());
+        assert_eq!(2, server.requests().len());
+
+        // The remaining 500, then a 429, then the default 200.
+        client
+            .push(
+                &payload,
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(5, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_per_push_timeout_and_retry() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::ok().with_delay(Duration::from_millis(300)));
+        server.enqueue(MockResponse::ok().with_delay(Duration::from_secs(30)));
+        let config = server
+            .config()
+            .unwrap()
+            .with_timeout(Duration::from_millis(100));
+        let mut client = APNClient::new(config).unwrap();
+        let payload = Payload::default();
+
+        let option = PushOption {
+            timeout: Some(Duration::from_secs(5)),
+            ..PushOption::alert("com.example.app")
+        };
+        client.push(&payload, "device-token", option).await.unwrap();
+
+        let error = client
+            .push(
+                &payload,
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, APNClientError::TimeoutError));
+
+        server.enqueue(MockResponse::error(500, "InternalServerError"));
+        let policy = RetryPolicy::new(2).with_backoff(Duration::ZERO, Duration::ZERO);
+        let option = PushOption {
+            retry_policy: Some(&policy),
+            ..PushOption::alert("com.example.app")
+        };
+        client.push(&payload, "device-token", option).await.unwrap();
+        assert_eq!(4, server.requests().len());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[tokio::test]
+    async fn test_retry_reuses_apns_id() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::error(503, "ServiceUnavailable"));
+        let policy = RetryPolicy::new(2).with_backoff(Duration::ZERO, Duration::ZERO);
+        let config = server.config().unwrap().with_retry_policy(policy);
+        let mut client = APNClient::new(config).unwrap();
+
+        client
+            .push(
+                &Payload::default(),
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap();
+        let ids = server
+            .requests()
+            .iter()
+            .map(|request| request.header("apns-id").unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(2, ids.len());
+        assert_eq!(ids[0], ids[1]);
+        assert_eq!(36, ids[0].len());
+        assert_eq!(Some('4'), ids[0].chars().nth(14));
+        assert_ne!(super::generate_apns_id(), super::generate_apns_id());
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_timeout() {
+        let server = MockServer::start().await;
+        let config = server
+            .config()
+            .unwrap()
+            .with_request_timeout(Duration::from_millis(50));
+        let mut client = APNClient::new(config).unwrap();
+        let payload = Payload::default();
+
+        // APNs may have accepted a push that timed out, so it is not retried by default.
+        server.enqueue(MockResponse::ok().with_delay(Duration::from_millis(500)));
+        let policy = RetryPolicy::new(2).with_backoff(Duration::ZERO, Duration::ZERO);
+        let option = PushOption {
+            retry_policy: Some(&policy),
+            ..PushOption::alert("com.example.app")
+        };
+        let error = client
+            .push(&payload, "device-token", option)
+            .await
+            .unwrap_err();
+        assert!(!error.is_retryable());
+        assert_eq!(1, server.requests().len());
+
+        server.enqueue(MockResponse::ok().with_delay(Duration::from_millis(500)));
+        let policy = policy.with_retry_after_send(true);
+        let option = PushOption {
+            retry_policy: Some(&policy),
+            ..PushOption::alert("com.example.app")
+        };
+        client.push(&payload, "device-token", option).await.unwrap();
+        let requests = server.requests();
+        assert_eq!(3, requests.len());
+        #[cfg(feature = "uuid")]
+        assert_eq!(
+            requests[1].header("apns-id").unwrap(),
+            requests[2].header("apns-id").unwrap()
+        );
+    }
+
+    #[cfg(feature = "uuid")]
+    #[tokio::test]
+    async fn test_push_with_id_uuid() {
+        let server = MockServer::start().await;
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let id = crate::Uuid::from_u128(0x0123_4567_89ab_4def_8123_4567_89ab_cdef);
+        let option = PushOption {
+            id_uuid: Some(id),
+            ..PushOption::alert("com.example.app")
+        };
+
+        let response = client
+            .push(&Payload::default(), "device-token", option)
+            .await
+            .unwrap();
+        assert_eq!(
+            Some("01234567-89ab-4def-8123-456789abcdef"),
+            server.last_request().unwrap().header("apns-id")
+        );
+        assert_eq!(Some(id), response.id_uuid());
+    }
+
+    #[tokio::test]
+    async fn test_push_broadcast() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::ok().with_header("apns-unique-id", "unique-1"));
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let option = PushOption {
+            collapse_id: Some("score"),
+            ..PushOption::live_activity("com.example.app")
+        };
+
+        let response = client
+            .push_broadcast(&Payload::default(), "channel-1", option)
+            .await
+            .unwrap();
+        assert_eq!(Some("unique-1"), response.unique_id.as_deref());
+
+        let request = server.last_request().unwrap();
+        assert_eq!("/4/broadcast/apps/com.example.app", request.path);
+        assert_eq!(Some("channel-1"), request.header("apns-channel-id"));
+        assert_eq!(Some("score"), request.header("apns-collapse-id"));
+        assert_eq!(Some("liveactivity"), request.header("apns-push-type"));
+        assert_eq!(None, request.header("apns-topic"));
+
+        server.enqueue(MockResponse::ok());
+        let response = client
+            .push_broadcast(&Payload::default(), "channel-1", option)
+            .await
+            .unwrap();
+        assert_eq!(None, response.unique_id);
+    }
+
+    #[tokio::test]
+    async fn test_push_on_another_runtime() {
+        let server = MockServer::start().await;
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let push = runtime.handle().spawn(async move {
+            let payload = Payload::default();
+            for _ in 0..2 {
+                client
+                    .push(
+                        &payload,
+                        "device-token",
+                        PushOption::alert("com.example.app"),
+                    )
+                    .await?;
+            }
+            Ok::<_, APNClientError>(client)
+        });
+        push.await.unwrap().unwrap();
+        assert_eq!(2, server.requests().len());
+        runtime.shutdown_background();
+    }
+
+    #[tokio::test]
+    async fn test_response_elapsed() {
+        let server = MockServer::start().await;
+        let delay = Duration::from_millis(50);
+        server.enqueue(MockResponse::ok().with_delay(delay));
+        server.enqueue(MockResponse::error(400, "BadDeviceToken").with_delay(delay));
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let payload = Payload::default();
+
+        let option = PushOption::alert("com.example.app");
+        let response = client.push(&payload, "device-token", option).await.unwrap();
+        assert!(response.elapsed() >= delay);
+
+        let option = PushOption::alert("com.example.app");
+        match client.push(&payload, "device-token", option).await {
+            Err(APNClientError::APNError { response, .. }) => assert!(response.elapsed() >= delay),
+            _ => panic!("expected an APNs error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_responses_from_mock_server() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::error(400, "BadDeviceToken"));
+        server.enqueue(MockResponse::gone(1_700_000_000));
+        server.enqueue(MockResponse::too_many_requests(30));
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let payload = Payload::default();
+
+        let mut errors = Vec::new();
+        for _ in 0..3 {
+            let option = PushOption::alert("com.example.app");
+            errors.push(
+                client
+                    .push(&payload, "device-token", option)
+                    .await
+                    .unwrap_err(),
+            );
+        }
+
+        match &errors[0] {
+            APNClientError::APNError { status, error, .. } => {
+                assert_eq!(ApnStatus::BadRequest, *status);
+                assert_eq!("BadDeviceToken", error.reason);
+            }
+            _ => panic!("expected an APNs error"),
+        }
+        match &errors[1] {
+            APNClientError::APNError { status, error, .. } => {
+                assert_eq!(ApnStatus::Gone, *status);
+                assert_eq!(Some(1_700_000_000), error.timestamp);
+            }
+            _ => panic!("expected an APNs error"),
+        }
+        assert!(errors[1].should_remove_token());
+        assert!(errors[2].is_retryable());
+        assert_eq!(3, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_error_body_keeps_id() {
+        let server = MockServer::start().await;
+        server.enqueue(
+            MockResponse::ok()
+                .with_status(500)
+                .with_header("apns-id", "6F8E2C3A-51C6-4D2A-9A7F-2B1E0C9D8A41")
+                .with_body("<html>Internal Server Error</html>"),
+        );
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+
+        let error = client
+            .push(
+                &Payload::default(),
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap_err();
+        match error {
+            APNClientError::InvalidResponseError {
+                response: Some(response),
+            } => assert_eq!("6F8E2C3A-51C6-4D2A-9A7F-2B1E0C9D8A41", response.id),
+            _ => panic!("expected an invalid response error with the apns-id"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_not_found_redacts_token() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::error(404, "BadPath"));
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+
+        let error = client
+            .push(
+                &Payload::default(),
+                "secret-device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap_err();
+        assert!(!error.to_string().contains("secret-device-token"));
+        assert!(!error.is_retryable());
+        match error {
+            APNClientError::RequestPathError {
+                status,
+                path,
+                error: Some(error),
+                ..
+            } => {
+                assert_eq!(ApnStatus::NotFound, status);
+                assert_eq!("/3/device/<redacted>", path);
+                assert_eq!(ApnReason::BadPath, error.kind());
+            }
+            _ => panic!("expected a request path error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_push_bytes() {
+        let server = MockServer::start().await;
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let payload = Payload::from(Notification::silent());
+        let body = payload.to_bytes().unwrap();
+
+        for device_token in ["first-token", "second-token"] {
+            client
+                .push_bytes(
+                    body.clone(),
+                    device_token,
+                    PushOption::background("com.example.app"),
+                )
+                .await
+                .unwrap();
+        }
+        let requests = server.requests();
+        assert_eq!(Some("second-token"), requests[1].device_token());
+        assert_eq!(Some("application/json"), requests[1].header("content-type"));
+        assert_eq!(payload.to_value().unwrap(), requests[1].json());
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::error(400, "BadDeviceToken"));
+        server.enqueue(MockResponse::error(403, "InvalidProviderToken"));
+        server.enqueue(MockResponse::error(503, "ServiceUnavailable"));
+        let config = server.config().unwrap().with_topic("com.example.app");
+        let mut client = APNClient::new(config).unwrap();
+
+        assert_eq!(Health::Ok, client.health_check().await.unwrap());
+        assert_eq!(
+            Health::AuthFailed(ApnReason::InvalidProviderToken),
+            client.health_check().await.unwrap()
+        );
+        assert_eq!(
+            Health::Unknown(ApnReason::ServiceUnavailable),
+            client.health_check().await.unwrap()
+        );
+        let request = server.last_request().unwrap();
+        assert_eq!(Some("background"), request.header("apns-push-type"));
+        assert_eq!(
+            serde_json::json!({ "aps": { "content-available": 1 } }),
+            request.json()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_health_check_without_topic() {
+        let server = MockServer::start().await;
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+
+        assert!(matches!(
+            client.health_check().await,
+            Err(APNClientError::MissingConfigError { field: "topic" })
+        ));
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_health_check_allowed_topics() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::error(400, "BadDeviceToken"));
+        let config = server
+            .config()
+            .unwrap()
+            .with_topic("com.example.app")
+            .with_allowed_topics(["com.example.app"]);
+        let mut client = APNClient::new(config).unwrap();
+        assert_eq!(Health::Ok, client.health_check().await.unwrap());
+        let request = server.last_request().unwrap();
+        assert_eq!(Some("com.example.app"), request.header("apns-topic"));
+
+        let config = server
+            .config()
+            .unwrap()
+            .with_topic("com.example.other")
+            .with_allowed_topics(["com.example.app"]);
+        let mut client = APNClient::new(config).unwrap();
+        assert!(matches!(
+            client.health_check().await,
+            Err(APNClientError::TopicNotAllowedError { topic }) if topic == "com.example.other"
+        ));
+        assert_eq!(1, server.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_push_raw_response() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::ok().with_header("apns-future", "value"));
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+
+        let res = client
+            .push_raw_response(
+                &Payload::default(),
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(200, res.status().as_u16());
+        assert_eq!("value", res.headers()["apns-future"]);
+        assert!(res.headers().contains_key("apns-id"));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_option() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::error(400, "BadCollapseId"));
+        server.enqueue(MockResponse::error(400, "BadMessageId"));
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let option = PushOption {
+            id: Some("not-a-uuid"),
+            collapse_id: Some("collapse"),
+            ..PushOption::alert("com.example.app")
+        };
+
+        for (field, value, reason) in [
+            (
+                RejectedField::CollapseId,
+                "collapse",
+                ApnReason::BadCollapseId,
+            ),
+            (RejectedField::Id, "not-a-uuid", ApnReason::BadMessageId),
+        ] {
+            let error = client
+                .push(&Payload::default(), "device-token", option)
+                .await
+                .unwrap_err();
+            assert_eq!(Some(reason), error.reason());
+            match error {
+                APNClientError::APNError {
+                    status,
+                    rejected: Some(rejected),
+                    ..
+                } => {
+                    assert_eq!(ApnStatus::BadRequest, status);
+                    assert_eq!(field, rejected.field);
+                    assert_eq!(value, rejected.value);
+                }
+                _ => panic!("expected an APNs error naming the rejected option"),
+            }
+        }
+
+        server.enqueue(MockResponse::error(400, "BadCollapseId"));
+        let config = server.config().unwrap().with_collapse_strategy(ContentHash);
+        let mut client = APNClient::new(config).unwrap();
+        let error = client
+            .push(
+                &Payload::default(),
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            APNClientError::APNError {
+                rejected: Some(rejected),
+                ..
+            } if rejected.field == RejectedField::CollapseStrategy
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_push_status_only() {
+        let server = MockServer::start().await;
+        server.enqueue(
+            MockResponse::ok()
+                .with_body("{}")
+                .with_body_delay(Duration::from_secs(30)),
+        );
+        server.enqueue(MockResponse::error(400, "BadDeviceToken"));
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let payload = Payload::default();
+
+        let status = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.push_status_only(
+                &payload,
+                "device-token",
+                PushOption::alert("com.example.app"),
+            ),
+        )
+        .await
+        .expect("headers should arrive without waiting for the body")
+        .unwrap();
+        assert!(status.is_success());
+        assert!(status.error().await.unwrap().is_none());
+
+        let status = client
+            .push_status_only(
+                &payload,
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(ApnStatus::BadRequest, status.status);
+        assert_eq!(
+            "BadDeviceToken",
+            status.error().await.unwrap().unwrap().reason
+        );
+    }
+
+    /// A local port refusing connections for as long as the returned socket lives, as it is
+    /// bound but not listening.
+    fn refusing_port() -> (TcpSocket, u16) {
+        let socket = TcpSocket::new_v4().unwrap();
+        socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let port = socket.local_addr().unwrap().port();
+        (socket, port)
+    }
+
+    struct FailingResolver;
+
+    impl Resolve for FailingResolver {
+        fn resolve(&self, _: Name) -> Resolving {
+            Box::pin(async { Err(io::Error::from(io::ErrorKind::NotFound).into()) })
+        }
+    }
+
+    async fn transport_failure(cfg: APNClientConfig) -> Option<TransportFailure> {
+        let mut client = APNClient::new(cfg).unwrap();
+        let error = client
+            .push(
+                &Payload::default(),
+                "device-token",
+                PushOption::alert("com.example.app"),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.is_retryable());
+        error.transport_failure()
+    }
+
+    #[tokio::test]
+    async fn test_transport_failure() {
+        let config = |endpoint: String| {
+            let endpoint = Endpoint::try_from(endpoint).unwrap();
+            APNClientConfig::new("TEAM_ID", "KEY_ID", TEST_KEY_PEM, endpoint).unwrap()
+        };
+
+        let mut cfg = config("api.push.apple.com:443".to_string());
+        cfg.resolver = Some(Arc::new(FailingResolver));
+        assert_eq!(Some(TransportFailure::Dns), transport_failure(cfg).await);
+
+        let (_socket, port) = refusing_port();
+        let cfg = config(format!("127.0.0.1:{}", port));
+        assert_eq!(
+            Some(TransportFailure::Connect),
+            transport_failure(cfg).await
+        );
+
+        // A plain HTTP server can not complete a TLS handshake.
+        let server = MockServer::start().await;
+        let cfg = config(format!("127.0.0.1:{}", server.addr().port()));
+        assert_eq!(Some(TransportFailure::Tls), transport_failure(cfg).await);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let source = reqwest::Client::new()
+            .get(format!("http://{}", addr))
+            .timeout(Duration::from_millis(50))
+            .send()
+            .await
+            .unwrap_err();
+        let error = APNClientError::HTTPError { source };
+        assert_eq!(Some(TransportFailure::Timeout), error.transport_failure());
+        assert_eq!(None, APNClientError::HeaderError.transport_failure());
+    }
 }
@@ -0,0 +1,93 @@
+use crate::Payload;
+use std::sync::Arc;
+
+/// The most bytes APNs accepts in an `apns-collapse-id`.
+pub const MAX_COLLAPSE_ID_LEN: usize = 64;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Derives an `apns-collapse-id` for pushes whose [`PushOption`](crate::PushOption) sets none.
+///
+/// Ids longer than [`MAX_COLLAPSE_ID_LEN`] bytes are truncated before they are sent.
+pub trait CollapseStrategy: Send + Sync {
+    fn collapse_id(&self, payload: &Payload) -> Option<String>;
+}
+
+/// Never sets a collapse id.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoCollapse;
+
+impl CollapseStrategy for NoCollapse {
+    fn collapse_id(&self, _payload: &Payload) -> Option<String> {
+        None
+    }
+}
+
+/// Collapses pushes with identical payloads, using a hash of the serialized payload.
+///
+/// The hash is FNV-1a, which is stable across processes and Rust versions, so every sender
+/// derives the same id for the same content.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ContentHash;
+
+impl CollapseStrategy for ContentHash {
+    fn collapse_id(&self, payload: &Payload) -> Option<String> {
+        let json = serde_json::to_vec(payload).ok()?;
+        let hash = json.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+        });
+        Some(format!("{:016x}", hash))
+    }
+}
+
+impl<T: CollapseStrategy + ?Sized> CollapseStrategy for Arc<T> {
+    fn collapse_id(&self, payload: &Payload) -> Option<String> {
+        (**self).collapse_id(payload)
+    }
+}
+
+/// Cuts `id` down to [`MAX_COLLAPSE_ID_LEN`] bytes without splitting a character.
+pub(crate) fn truncate_collapse_id(mut id: String) -> String {
+    if id.len() > MAX_COLLAPSE_ID_LEN {
+        let mut end = MAX_COLLAPSE_ID_LEN;
+        while !id.is_char_boundary(end) {
+            end -= 1;
+        }
+        id.truncate(end);
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collapse::truncate_collapse_id;
+    use crate::{Alert, CollapseStrategy, ContentHash, Notification, Payload};
+
+    fn payload(body: &str) -> Payload {
+        Payload::from(Notification {
+            alert: Some(Alert::Body(body.to_string())),
+            ..Notification::default()
+        })
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let id = ContentHash.collapse_id(&payload("Hello")).unwrap();
+        assert_eq!(16, id.len());
+        assert_eq!(Some(id.clone()), ContentHash.collapse_id(&payload("Hello")));
+        assert_ne!(Some(id), ContentHash.collapse_id(&payload("Bye")));
+    }
+
+    #[test]
+    fn test_truncate_collapse_id() {
+        assert_eq!("short", truncate_collapse_id("short".to_string()));
+        assert_eq!(64, truncate_collapse_id("a".repeat(100)).len());
+        let id = truncate_collapse_id("é".repeat(40));
+        assert_eq!(64, id.len());
+        assert_eq!(
+            63,
+            truncate_collapse_id(format!("a{}", "é".repeat(40))).len()
+        );
+    }
+}
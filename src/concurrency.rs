@@ -0,0 +1,85 @@
+const DEFAULT_MIN_CONCURRENCY: usize = 1;
+const DEFAULT_MAX_CONCURRENCY: usize = 100;
+
+/// An AIMD controller for how many requests [`APNClient::push_many`](crate::APNClient::push_many)
+/// keeps in flight.
+///
+/// The limit starts at the maximum. Every `429 TooManyRequests` halves it, and once as many
+/// pushes as the current limit succeed in a row it grows by one again, never leaving
+/// `min..=max`.
+#[derive(Debug, Clone)]
+pub struct AdaptiveConcurrency {
+    min: usize,
+    max: usize,
+    limit: usize,
+    successes: usize,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        let max = max.max(min);
+        Self {
+            min,
+            max,
+            limit: max,
+            successes: 0,
+        }
+    }
+
+    /// The number of requests currently allowed in flight.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub(crate) fn on_success(&mut self) {
+        self.successes += 1;
+        if self.successes >= self.limit {
+            self.limit = (self.limit + 1).min(self.max);
+            self.successes = 0;
+        }
+    }
+
+    pub(crate) fn on_throttled(&mut self) {
+        self.limit = (self.limit / 2).max(self.min);
+        self.successes = 0;
+    }
+}
+
+impl Default for AdaptiveConcurrency {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_CONCURRENCY, DEFAULT_MAX_CONCURRENCY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AdaptiveConcurrency;
+
+    #[test]
+    fn test_aimd() {
+        let mut concurrency = AdaptiveConcurrency::new(2, 16);
+        assert_eq!(16, concurrency.limit());
+
+        concurrency.on_throttled();
+        concurrency.on_throttled();
+        assert_eq!(4, concurrency.limit());
+        concurrency.on_throttled();
+        concurrency.on_throttled();
+        assert_eq!(2, concurrency.limit());
+
+        concurrency.on_success();
+        assert_eq!(2, concurrency.limit());
+        concurrency.on_success();
+        assert_eq!(3, concurrency.limit());
+        for _ in 0..3 {
+            concurrency.on_success();
+        }
+        assert_eq!(4, concurrency.limit());
+
+        for _ in 0..1000 {
+            concurrency.on_success();
+        }
+        assert_eq!(16, concurrency.limit());
+    }
+}
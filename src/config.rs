@@ -0,0 +1,163 @@
+use crate::APNClientError::MissingConfigError;
+use crate::{
+    APNClientConfig, APNClientError, DerKey, Endpoint, KeySource, Metrics, PemKey, RetryPolicy,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Collects an [`APNClientConfig`] field by field, checking the required ones in
+/// [`build`](Self::build).
+///
+/// The team id, key id, key and endpoint are required. Knobs not covered here can still be set
+/// on the built config with its `with_*` methods.
+#[derive(Default)]
+pub struct APNClientConfigBuilder {
+    team_id: Option<String>,
+    key_id: Option<String>,
+    key: Option<Box<dyn KeySource + Send + Sync>>,
+    endpoint: Option<Endpoint>,
+    topic: Option<String>,
+    proxy: Option<String>,
+    retry_policy: Option<RetryPolicy>,
+    timeout: Option<Duration>,
+    metrics: Option<Arc<dyn Metrics>>,
+    verify_on_build: bool,
+}
+
+impl APNClientConfig {
+    pub fn builder() -> APNClientConfigBuilder {
+        APNClientConfigBuilder::default()
+    }
+}
+
+impl APNClientConfigBuilder {
+    pub fn with_team_id(mut self, team_id: &str) -> Self {
+        self.team_id = Some(team_id.to_string());
+        self
+    }
+
+    pub fn with_key_id(mut self, key_id: &str) -> Self {
+        self.key_id = Some(key_id.to_string());
+        self
+    }
+
+    /// Signs with a PEM encoded key, such as the contents of the `.p8` file.
+    pub fn with_pem_key(self, pem: &str) -> Self {
+        self.with_key_source(PemKey::new(pem))
+    }
+
+    /// Signs with a PKCS #8 DER encoded key.
+    pub fn with_der_key(self, der: &[u8]) -> Self {
+        self.with_key_source(DerKey::new(der))
+    }
+
+    /// Signs with a key from any [`KeySource`], loaded in [`build`](Self::build).
+    pub fn with_key_source(mut self, source: impl KeySource + Send + Sync + 'static) -> Self {
+        self.key = Some(Box::new(source));
+        self
+    }
+
+    pub fn with_endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.endpoint = Some(endpoint);
+        self
+    }
+
+    /// See [`APNClientConfig::with_topic`].
+    pub fn with_topic(mut self, topic: &str) -> Self {
+        self.topic = Some(topic.to_string());
+        self
+    }
+
+    /// See [`APNClientConfig::with_proxy`]; the URL is checked in [`build`](Self::build).
+    pub fn with_proxy(mut self, url: &str) -> Self {
+        self.proxy = Some(url.to_string());
+        self
+    }
+
+    /// See [`APNClientConfig::with_retry_policy`].
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// See [`APNClientConfig::with_timeout`].
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// See [`APNClientConfig::with_metrics`].
+    pub fn with_metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+        self.metrics = Some(Arc::new(metrics));
+        self
+    }
+
+    /// See [`APNClientConfig::with_verify_on_build`].
+    pub fn with_verify_on_build(mut self, verify: bool) -> Self {
+        self.verify_on_build = verify;
+        self
+    }
+
+    /// Loads the key and builds the config, naming the first required field that is missing.
+    pub fn build(self) -> Result<APNClientConfig, APNClientError> {
+        let team_id = self
+            .team_id
+            .ok_or(MissingConfigError { field: "team_id" })?;
+        let key_id = self.key_id.ok_or(MissingConfigError { field: "key_id" })?;
+        let key = self.key.ok_or(MissingConfigError { field: "key" })?;
+        let endpoint = self
+            .endpoint
+            .ok_or(MissingConfigError { field: "endpoint" })?;
+        let mut config = APNClientConfig::from_source(&team_id, &key_id, &*key, endpoint)?
+            .with_verify_on_build(self.verify_on_build);
+        if let Some(topic) = self.topic {
+            config = config.with_topic(&topic);
+        }
+        if let Some(url) = self.proxy {
+            config = config.with_proxy(&url)?;
+        }
+        if let Some(policy) = self.retry_policy {
+            config = config.with_retry_policy(policy);
+        }
+        if let Some(timeout) = self.timeout {
+            config = config.with_timeout(timeout);
+        }
+        if let Some(metrics) = self.metrics {
+            config = config.with_metrics(metrics);
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TEST_KEY_PEM;
+    use crate::{APNClient, APNClientConfig, APNClientError, Endpoint};
+
+    #[test]
+    fn test_builder() {
+        let config = APNClientConfig::builder()
+            .with_team_id("TEAM_ID")
+            .with_key_id("KEY_ID")
+            .with_pem_key(TEST_KEY_PEM)
+            .with_endpoint(Endpoint::development())
+            .with_topic("com.example.app")
+            .with_verify_on_build(true)
+            .build()
+            .unwrap();
+        assert!(APNClient::new(config).is_ok());
+    }
+
+    #[test]
+    fn test_builder_missing_field() {
+        let result = APNClientConfig::builder()
+            .with_team_id("TEAM_ID")
+            .with_pem_key(TEST_KEY_PEM)
+            .with_endpoint(Endpoint::development())
+            .build();
+        assert!(matches!(
+            result,
+            Err(APNClientError::MissingConfigError { field: "key_id" })
+        ));
+    }
+}
@@ -0,0 +1,92 @@
+use crate::Metrics;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use snafu::Snafu;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Wraps the connector of the HTTP client to report every new connection, which would otherwise
+/// be invisible behind the connection pool.
+#[derive(Clone)]
+pub(crate) struct ConnectLayer {
+    pub(crate) metrics: Arc<dyn Metrics>,
+}
+
+impl<S> Layer<S> for ConnectLayer {
+    type Service = ConnectService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ConnectService<S> {
+    inner: S,
+    metrics: Arc<dyn Metrics>,
+}
+
+impl<S, R> Service<R> for ConnectService<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let start = Instant::now();
+        let metrics = self.metrics.clone();
+        let connect = self.inner.call(req);
+        Box::pin(async move {
+            let conn = connect.await?;
+            let elapsed = start.elapsed();
+            tracing::debug!(?elapsed, "Opened a new connection to APN server");
+            metrics.connection_opened(elapsed);
+            Ok(conn)
+        })
+    }
+}
+
+/// A failed host name lookup, marked so
+/// [`TransportFailure`](crate::TransportFailure) can tell it from other connect errors.
+#[derive(Debug, Snafu)]
+#[snafu(display("Unable to resolve host name"))]
+pub(crate) struct DnsError {
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+/// Resolves host names with the system resolver, or `inner` if set, wrapping failures in
+/// [`DnsError`].
+pub(crate) struct DnsResolver {
+    pub(crate) inner: Option<Arc<dyn Resolve>>,
+}
+
+impl Resolve for DnsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let addrs = match inner {
+                Some(inner) => inner.resolve(name).await,
+                None => tokio::net::lookup_host((name.as_str(), 0))
+                    .await
+                    .map(|addrs| Box::new(addrs.collect::<Vec<_>>().into_iter()) as Addrs)
+                    .map_err(|error: io::Error| error.into()),
+            };
+            addrs.map_err(|source| DnsError { source }.into())
+        })
+    }
+}
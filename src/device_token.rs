@@ -0,0 +1,100 @@
+use snafu::{Snafu, ensure};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Snafu, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeviceTokenError {
+    #[snafu(display("Device token is empty"))]
+    EmptyTokenError,
+    #[snafu(display("Device token has a non-hex character at {}", position))]
+    NonHexTokenError { position: usize },
+    #[snafu(display("Device token has an odd number of hex digits"))]
+    OddLengthTokenError,
+}
+
+/// A device token checked to be well-formed hex, as APNs hands them out.
+///
+/// The length is not checked, since Apple does not guarantee one. A well-formed token may still
+/// be one APNs no longer accepts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeviceToken(String);
+
+impl DeviceToken {
+    pub fn parse(token: &str) -> Result<Self, DeviceTokenError> {
+        ensure!(!token.is_empty(), EmptyTokenSnafu);
+        if let Some(position) = token.bytes().position(|b| !b.is_ascii_hexdigit()) {
+            return NonHexTokenSnafu { position }.fail();
+        }
+        ensure!(token.len().is_multiple_of(2), OddLengthTokenSnafu);
+        Ok(Self(token.to_string()))
+    }
+
+    /// Splits `tokens` into the well-formed ones and the rest with the reason they were
+    /// rejected, before a large fan-out wastes requests on them. Order is kept in both.
+    pub fn validate_all<'a>(
+        tokens: impl IntoIterator<Item = &'a str>,
+    ) -> (Vec<DeviceToken>, Vec<(String, DeviceTokenError)>) {
+        let mut valid = Vec::new();
+        let mut invalid = Vec::new();
+        for token in tokens {
+            match Self::parse(token) {
+                Ok(token) => valid.push(token),
+                Err(error) => invalid.push((token.to_string(), error)),
+            }
+        }
+        (valid, invalid)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for DeviceToken {
+    type Err = DeviceTokenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl AsRef<str> for DeviceToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for DeviceToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DeviceToken, DeviceTokenError};
+
+    #[test]
+    fn test_validate_all() {
+        let good = "ab".repeat(32);
+        let tokens = [good.as_str(), "", "abc", "zz00", "00ff"];
+        let (valid, invalid) = DeviceToken::validate_all(tokens);
+
+        assert_eq!(
+            vec![good.as_str(), "00ff"],
+            valid.iter().map(DeviceToken::as_str).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                (String::new(), DeviceTokenError::EmptyTokenError),
+                ("abc".to_string(), DeviceTokenError::OddLengthTokenError),
+                (
+                    "zz00".to_string(),
+                    DeviceTokenError::NonHexTokenError { position: 0 }
+                ),
+            ],
+            invalid
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use crate::APNClientError::EnvironmentError;
+use crate::{APNClientConfig, APNClientError, Endpoint, KeySource, PemFile, PemKey};
+use std::env;
+
+impl APNClientConfig {
+    /// Builds a configuration from `APNS_*` environment variables.
+    ///
+    /// | Variable           | Meaning                                                |
+    /// |--------------------|--------------------------------------------------------|
+    /// | `APNS_TEAM_ID`     | Team id, required                                      |
+    /// | `APNS_KEY_ID`      | Key id, required                                       |
+    /// | `APNS_KEY`         | The `.p8` key itself, `\n` escapes allowed             |
+    /// | `APNS_KEY_PATH`    | Path to the `.p8` key, if `APNS_KEY` is not set        |
+    /// | `APNS_ENVIRONMENT` | `sandbox` (or `development`) or `production`, required |
+    /// | `APNS_TOPIC`       | Default topic, see [`with_topic`](Self::with_topic)    |
+    ///
+    /// `APNS_ENV` is accepted in place of `APNS_ENVIRONMENT`. Errors name the offending variable.
+    ///
+    /// Only available with the `env` feature, so a stray variable never changes configuration
+    /// that was not asked to come from the environment.
+    pub fn from_env() -> Result<Self, APNClientError> {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, APNClientError> {
+        let get = |name: &str| lookup(name).filter(|value| !value.trim().is_empty());
+        let require = |name: &str| get(name).ok_or_else(|| invalid(name, "not set"));
+
+        let team_id = require("APNS_TEAM_ID")?;
+        let key_id = require("APNS_KEY_ID")?;
+        let key: Box<dyn KeySource> = match (get("APNS_KEY"), get("APNS_KEY_PATH")) {
+            (Some(pem), None) => Box::new(PemKey::new(pem.replace("\\n", "\n"))),
+            (None, Some(path)) => Box::new(PemFile::new(path)),
+            (Some(_), Some(_)) => {
+                return Err(invalid(
+                    "APNS_KEY",
+                    "set only one of APNS_KEY and APNS_KEY_PATH",
+                ));
+            }
+            (None, None) => return Err(invalid("APNS_KEY", "not set, nor is APNS_KEY_PATH")),
+        };
+        let (name, environment) = match get("APNS_ENVIRONMENT") {
+            Some(environment) => ("APNS_ENVIRONMENT", environment),
+            None => (
+                "APNS_ENV",
+                get("APNS_ENV").ok_or_else(|| invalid("APNS_ENVIRONMENT", "not set"))?,
+            ),
+        };
+        let endpoint = match environment.trim() {
+            "sandbox" | "development" => Endpoint::development(),
+            "production" => Endpoint::production(),
+            other => {
+                let msg = format!("expected sandbox or production, got {}", other);
+                return Err(invalid(name, &msg));
+            }
+        };
+
+        let mut config = Self::from_source(&team_id, &key_id, &*key, endpoint)?;
+        if let Some(topic) = get("APNS_TOPIC") {
+            config = config.with_topic(&topic);
+        }
+        Ok(config)
+    }
+}
+
+fn invalid(name: &str, msg: &str) -> APNClientError {
+    EnvironmentError {
+        name: name.to_string(),
+        msg: msg.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TEST_KEY_PEM;
+    use crate::{APNClientConfig, APNClientError};
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn from_vars(vars: &[(&str, &str)]) -> Result<APNClientConfig, APNClientError> {
+        let vars = HashMap::<_, _>::from_iter(vars.iter().copied());
+        APNClientConfig::from_lookup(|name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    fn missing(result: Result<APNClientConfig, APNClientError>) -> String {
+        match result {
+            Err(APNClientError::EnvironmentError { name, .. }) => name,
+            _ => panic!("expected an environment error"),
+        }
+    }
+
+    #[test]
+    fn test_from_env() {
+        let path = std::env::temp_dir().join(format!("apnoxide-env-{}.p8", std::process::id()));
+        fs::write(&path, TEST_KEY_PEM).unwrap();
+        let key_path = path.to_str().unwrap();
+        let vars = [
+            ("APNS_TEAM_ID", "TEAM_ID"),
+            ("APNS_KEY_ID", "KEY_ID"),
+            ("APNS_KEY_PATH", key_path),
+            ("APNS_ENV", "sandbox"),
+            ("APNS_TOPIC", "com.example.app"),
+        ];
+
+        let config = from_vars(&vars).unwrap();
+        assert_eq!(Some("com.example.app"), config.topic());
+        assert_eq!("APNS_TEAM_ID", missing(from_vars(&vars[1..])));
+        assert_eq!("APNS_KEY", missing(from_vars(&vars[..2])));
+        assert_eq!("APNS_ENVIRONMENT", missing(from_vars(&vars[..3])));
+        assert_eq!(
+            "APNS_ENV",
+            missing(from_vars(&[
+                vars[0],
+                vars[1],
+                vars[2],
+                ("APNS_ENV", "staging")
+            ]))
+        );
+        assert!(from_vars(&vars[..4]).unwrap().topic().is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_from_env_inline_key() {
+        let escaped = TEST_KEY_PEM.replace('\n', "\\n");
+        let vars = [
+            ("APNS_TEAM_ID", "TEAM_ID"),
+            ("APNS_KEY_ID", "KEY_ID"),
+            ("APNS_KEY", escaped.as_str()),
+            ("APNS_ENVIRONMENT", "production"),
+        ];
+        assert!(from_vars(&vars).is_ok());
+
+        let both = [
+            vars[0],
+            vars[1],
+            vars[2],
+            vars[3],
+            ("APNS_KEY_PATH", "key.p8"),
+        ];
+        assert_eq!("APNS_KEY", missing(from_vars(&both)));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::BuildError::{CompressionError, DeserializeError};
+use crate::{BuildError, Payload};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::io::Write;
+
+impl Payload {
+    /// The serialized payload, gzip compressed for storing or logging.
+    ///
+    /// APNs does not accept compressed requests, so pushes are always sent uncompressed.
+    pub fn to_gzip(&self) -> Result<Vec<u8>, BuildError> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, self).map_err(compression_error)?;
+        encoder.flush().map_err(compression_error)?;
+        encoder.finish().map_err(compression_error)
+    }
+
+    /// Reads back a payload compressed with [`to_gzip`](Self::to_gzip).
+    pub fn from_gzip(bytes: &[u8]) -> Result<Self, BuildError> {
+        serde_json::from_reader(GzDecoder::new(bytes)).map_err(|source| {
+            if source.is_io() {
+                compression_error(source)
+            } else {
+                DeserializeError { source }
+            }
+        })
+    }
+}
+
+fn compression_error(source: impl Into<std::io::Error>) -> BuildError {
+    CompressionError {
+        source: source.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Alert, Notification, Payload};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_gzip_round_trip() {
+        let payload = Payload::from(Notification {
+            alert: Some(Alert::Body("Hello".repeat(100))),
+            ..Notification::default()
+        })
+        .with_custom(HashMap::from([("id", 7)]))
+        .unwrap();
+        let json = serde_json::to_vec(&payload).unwrap();
+
+        let compressed = payload.to_gzip().unwrap();
+        assert!(compressed.len() < json.len());
+        let restored = Payload::from_gzip(&compressed).unwrap();
+        assert_eq!(json, serde_json::to_vec(&restored).unwrap());
+
+        assert!(Payload::from_gzip(b"not gzip").is_err());
+    }
+
+    #[test]
+    fn test_gzip_round_trip_without_aps() {
+        let payload = Payload::mdm("push-magic").unwrap();
+        let restored = Payload::from_gzip(&payload.to_gzip().unwrap()).unwrap();
+        assert_eq!(
+            r#"{"mdm":"push-magic"}"#,
+            serde_json::to_string(&restored).unwrap()
+        );
+    }
+}
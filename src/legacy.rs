@@ -0,0 +1,118 @@
+//! The legacy binary provider API format, for bridging to systems that still expect it.
+//!
+//! Apple has retired the binary API; push with [`APNClient`](crate::APNClient) over HTTP/2
+//! wherever possible. This only produces the bytes of a notification frame, command `2`.
+
+use crate::{BuildError, Payload};
+use snafu::{OptionExt, ResultExt, Snafu, ensure};
+
+const COMMAND: u8 = 2;
+const MAX_PAYLOAD_LEN: usize = 2048;
+const TOKEN_LEN: usize = 32;
+
+#[derive(Snafu, Debug)]
+#[non_exhaustive]
+pub enum LegacyError {
+    #[snafu(display("Device token must be {} bytes of hex", TOKEN_LEN))]
+    InvalidTokenError,
+    #[snafu(display(
+        "Payload of {} bytes exceeds the legacy limit of {}",
+        size,
+        MAX_PAYLOAD_LEN
+    ))]
+    PayloadTooLargeError { size: usize },
+    #[snafu(display("Invalid payload: {}", source))]
+    InvalidPayloadError { source: BuildError },
+}
+
+/// One notification in the legacy binary format.
+pub struct LegacyNotification<'a> {
+    /// The device token as 64 hex characters.
+    pub device_token: &'a str,
+    pub payload: &'a Payload,
+    /// Echoed back by the legacy API in error responses.
+    pub identifier: u32,
+    /// Seconds since the UNIX epoch, or `0` to not store the notification.
+    pub expiration: u32,
+    /// `10` to deliver immediately, `5` to deliver at a power-conserving time.
+    pub priority: u8,
+}
+
+impl LegacyNotification<'_> {
+    /// The complete frame: the command, the frame length and the token, payload, identifier,
+    /// expiration and priority items, all big-endian.
+    pub fn encode(&self) -> Result<Vec<u8>, LegacyError> {
+        let token = decode_hex(self.device_token).context(InvalidTokenSnafu)?;
+        ensure!(token.len() == TOKEN_LEN, InvalidTokenSnafu);
+        let payload = self.payload.to_bytes().context(InvalidPayloadSnafu)?;
+        ensure!(
+            payload.len() <= MAX_PAYLOAD_LEN,
+            PayloadTooLargeSnafu {
+                size: payload.len()
+            }
+        );
+
+        let mut frame = Vec::with_capacity(TOKEN_LEN + payload.len() + 24);
+        let mut item = |id: u8, data: &[u8]| {
+            frame.push(id);
+            frame.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            frame.extend_from_slice(data);
+        };
+        item(1, &token);
+        item(2, &payload);
+        item(3, &self.identifier.to_be_bytes());
+        item(4, &self.expiration.to_be_bytes());
+        item(5, &[self.priority]);
+
+        let mut bytes = Vec::with_capacity(frame.len() + 5);
+        bytes.push(COMMAND);
+        bytes.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&frame);
+        Ok(bytes)
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Payload;
+    use crate::legacy::{LegacyError, LegacyNotification};
+
+    #[test]
+    fn test_encode() {
+        let payload = Payload::default();
+        let notification = LegacyNotification {
+            device_token: &"ab".repeat(32),
+            payload: &payload,
+            identifier: 7,
+            expiration: 0x5f5e_1000,
+            priority: 10,
+        };
+        let mut expected = vec![2, 0, 0, 0, 66, 1, 0, 32];
+        expected.extend_from_slice(&[0xab; 32]);
+        expected.extend_from_slice(&[2, 0, 10]);
+        expected.extend_from_slice(br#"{"aps":{}}"#);
+        expected.extend_from_slice(&[3, 0, 4, 0, 0, 0, 7]);
+        expected.extend_from_slice(&[4, 0, 4, 0x5f, 0x5e, 0x10, 0x00]);
+        expected.extend_from_slice(&[5, 0, 1, 10]);
+        assert_eq!(expected, notification.encode().unwrap());
+
+        let notification = LegacyNotification {
+            device_token: "not-hex",
+            ..notification
+        };
+        assert!(matches!(
+            notification.encode(),
+            Err(LegacyError::InvalidTokenError)
+        ));
+    }
+}
@@ -1,6 +1,45 @@
+mod batch;
+mod builder;
 mod client;
+mod clock;
+mod collapse;
+mod concurrency;
+mod config;
+mod connect;
+mod device_token;
+#[cfg(any(test, feature = "env"))]
+mod env;
+#[cfg(any(test, feature = "gzip"))]
+mod gzip;
+mod key;
+#[cfg(any(test, feature = "legacy"))]
+pub mod legacy;
+mod live_activity;
+mod metrics;
+mod rate_limit;
+mod registry;
+mod retry;
 pub mod serialize;
+mod status;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 mod types;
 
+pub use batch::*;
+pub use builder::*;
 pub use client::*;
+pub use clock::*;
+pub use collapse::*;
+pub use concurrency::*;
+pub use config::*;
+pub use device_token::*;
+pub use key::*;
+pub use live_activity::*;
+pub use metrics::*;
+pub use rate_limit::*;
+pub use registry::*;
+pub use retry::*;
+pub use status::*;
 pub use types::*;
+#[cfg(feature = "uuid")]
+pub use uuid::Uuid;
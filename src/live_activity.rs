@@ -0,0 +1,146 @@
+use crate::{APNClient, APNClientError, APNResponse, Payload, PushOption};
+use std::time::UNIX_EPOCH;
+
+/// Where the updates of one Live Activity are sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiveActivityTarget {
+    /// The activity's push token.
+    Device(String),
+    /// The broadcast channel the activity subscribes to.
+    Channel(String),
+}
+
+/// Sends the updates of one Live Activity with strictly increasing timestamps.
+///
+/// The device drops an update whose `timestamp` is not newer than the last one it shows, so two
+/// updates generated within the same second would lose the second. The session bumps such a
+/// timestamp to one past the last sent.
+#[derive(Debug, Clone)]
+pub struct LiveActivitySession {
+    target: LiveActivityTarget,
+    last_timestamp: Option<u64>,
+}
+
+impl LiveActivitySession {
+    pub fn new(target: LiveActivityTarget) -> Self {
+        Self {
+            target,
+            last_timestamp: None,
+        }
+    }
+
+    pub fn target(&self) -> &LiveActivityTarget {
+        &self.target
+    }
+
+    /// The timestamp of the last update sent, or `None` before the first.
+    pub fn last_timestamp(&self) -> Option<u64> {
+        self.last_timestamp
+    }
+
+    /// Sends `payload` to the activity, first moving its `timestamp`, or the current time of the
+    /// client's [`Clock`](crate::Clock) if it has none, past the last update sent.
+    ///
+    /// The session only remembers the timestamp once APNs accepted the update.
+    pub async fn push(
+        &mut self,
+        client: &mut APNClient,
+        payload: &mut Payload,
+        option: PushOption<'_>,
+    ) -> Result<APNResponse, APNClientError> {
+        let timestamp = payload.aps.timestamp.unwrap_or_else(|| {
+            client
+                .clock()
+                .now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        });
+        let timestamp = match self.last_timestamp {
+            Some(last) if timestamp <= last => last + 1,
+            _ => timestamp,
+        };
+        payload.aps.timestamp = Some(timestamp);
+        let response = match &self.target {
+            LiveActivityTarget::Device(token) => client.push(payload, token, option).await?,
+            LiveActivityTarget::Channel(channel_id) => client
+                .push_broadcast(payload, channel_id, option)
+                .await?
+                .into(),
+        };
+        self.last_timestamp = Some(timestamp);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{MockResponse, MockServer};
+    use crate::{
+        APNClient, Clock, LiveActivitySession, LiveActivityTarget, Notification, Payload,
+        PushOption,
+    };
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    #[tokio::test]
+    async fn test_increasing_timestamps() {
+        let server = MockServer::start().await;
+        let mut client = APNClient::new(server.config().unwrap()).unwrap();
+        let target = LiveActivityTarget::Device("activity-token".to_string());
+        let mut session = LiveActivitySession::new(target);
+        let option = PushOption::live_activity("com.example.app");
+
+        for timestamp in [100, 100, 100, 50, 200] {
+            let state = serde_json::json!({ "home": 1 });
+            let aps = Notification::live_activity_update(&state, &state, timestamp).unwrap();
+            let mut payload = Payload::from(aps);
+            session
+                .push(&mut client, &mut payload, option)
+                .await
+                .unwrap();
+        }
+
+        let sent = server
+            .requests()
+            .iter()
+            .map(|request| request.json()["aps"]["timestamp"].as_u64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![100, 101, 102, 103, 200], sent);
+        assert_eq!(Some(200), session.last_timestamp());
+    }
+
+    struct FixedClock;
+
+    impl Clock for FixedClock {
+        fn now(&self) -> SystemTime {
+            UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_from_clock() {
+        let server = MockServer::start().await;
+        server.enqueue(MockResponse::error(400, "BadDeviceToken"));
+        let mut client = APNClient::new(server.config().unwrap().with_clock(FixedClock)).unwrap();
+        let target = LiveActivityTarget::Device("activity-token".to_string());
+        let mut session = LiveActivitySession::new(target);
+        let option = PushOption::live_activity("com.example.app");
+
+        let mut payload = Payload::default();
+        assert!(
+            session
+                .push(&mut client, &mut payload, option)
+                .await
+                .is_err()
+        );
+        assert_eq!(Some(1_700_000_000), payload.aps.timestamp);
+        assert_eq!(None, session.last_timestamp());
+
+        let mut payload = Payload::default();
+        session
+            .push(&mut client, &mut payload, option)
+            .await
+            .unwrap();
+        assert_eq!(Some(1_700_000_000), session.last_timestamp());
+    }
+}
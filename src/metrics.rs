@@ -0,0 +1,64 @@
+use serde::Serialize;
+use serde_with::{DurationSecondsWithFrac, serde_as};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Hooks for recording client internals in a metrics system.
+///
+/// Every method has an empty default, so implementations only override what they record.
+pub trait Metrics: Send + Sync {
+    /// A provider token was signed, taking `elapsed`.
+    fn token_signed(&self, elapsed: Duration) {
+        let _ = elapsed;
+    }
+
+    /// A new connection to APNs was established, taking `elapsed` including the TLS handshake.
+    ///
+    /// Pushes otherwise reuse pooled connections, so frequent calls point at reconnections.
+    fn connection_opened(&self, elapsed: Duration) {
+        let _ = elapsed;
+    }
+
+    /// APNs answered a push, successfully or not.
+    fn push_completed(&self, event: &PushEvent) {
+        let _ = event;
+    }
+}
+
+/// One push APNs answered, for structured logs and [`Metrics::push_completed`].
+///
+/// Carries no device token, provider token or payload, so it is safe to log as is.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PushEvent {
+    pub apns_id: Option<String>,
+    pub status: u16,
+    /// The reason APNs gave for rejecting the push.
+    pub reason: Option<String>,
+    pub push_type: Option<String>,
+    /// The `apns-topic`, [redacted](crate::Topic::redacted) so it does not name the app.
+    pub topic_redacted: Option<String>,
+    /// From sending the request until the response was read, in seconds when serialized.
+    #[serde_as(as = "DurationSecondsWithFrac<f64>")]
+    pub latency: Duration,
+}
+
+/// Records nothing.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {}
+
+impl<T: Metrics + ?Sized> Metrics for Arc<T> {
+    fn token_signed(&self, elapsed: Duration) {
+        (**self).token_signed(elapsed)
+    }
+
+    fn connection_opened(&self, elapsed: Duration) {
+        (**self).connection_opened(elapsed)
+    }
+
+    fn push_completed(&self, event: &PushEvent) {
+        (**self).push_completed(event)
+    }
+}
@@ -0,0 +1,161 @@
+use std::mem;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A client-side cap on how fast pushes are sent, in requests and/or bytes per second.
+///
+/// Each limit is a token bucket allowing bursts of up to one second's worth. Pushes beyond it
+/// wait for the bucket to refill rather than fail.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateLimit {
+    requests_per_second: Option<u32>,
+    bytes_per_second: Option<u64>,
+}
+
+impl RateLimit {
+    pub fn requests_per_second(rate: u32) -> Self {
+        Self::default().with_requests_per_second(rate)
+    }
+
+    pub fn bytes_per_second(rate: u64) -> Self {
+        Self::default().with_bytes_per_second(rate)
+    }
+
+    /// Also caps the number of requests; a `rate` of `0` is treated as `1`.
+    pub fn with_requests_per_second(mut self, rate: u32) -> Self {
+        self.requests_per_second = Some(rate.max(1));
+        self
+    }
+
+    /// Also caps the payload bytes sent; a `rate` of `0` is treated as `1`.
+    pub fn with_bytes_per_second(mut self, rate: u64) -> Self {
+        self.bytes_per_second = Some(rate.max(1));
+        self
+    }
+}
+
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            updated: now,
+        }
+    }
+
+    /// Takes `amount` tokens, going into debt if there are not enough, and returns how long
+    /// until the debt is paid off.
+    fn take(&mut self, amount: f64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate) - amount;
+        self.updated = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    /// Gives back `amount` tokens taken for a request that was never sent.
+    fn refund(&mut self, amount: f64) {
+        self.tokens = (self.tokens + amount).min(self.rate);
+    }
+}
+
+pub(crate) struct RateLimiter {
+    requests: Option<Mutex<Bucket>>,
+    bytes: Option<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        let now = Instant::now();
+        Self {
+            requests: limit
+                .requests_per_second
+                .map(|rate| Mutex::new(Bucket::new(rate as f64, now))),
+            bytes: limit
+                .bytes_per_second
+                .map(|rate| Mutex::new(Bucket::new(rate as f64, now))),
+        }
+    }
+
+    /// Waits until a request with a body of `bytes` fits within the limit.
+    ///
+    /// The tokens are taken up front so concurrent requests queue in order, and refunded if the
+    /// wait is cancelled, e.g. by a timeout, so a cancelled push does not delay later ones.
+    pub(crate) async fn acquire(&self, bytes: usize) {
+        let now = Instant::now();
+        let take = |bucket: &Option<Mutex<Bucket>>, amount: f64| match bucket {
+            Some(bucket) => bucket.lock().unwrap().take(amount, now),
+            None => Duration::ZERO,
+        };
+        let delay = take(&self.requests, 1.0).max(take(&self.bytes, bytes as f64));
+        if !delay.is_zero() {
+            let refund = Refund {
+                limiter: self,
+                bytes,
+            };
+            tokio::time::sleep(delay).await;
+            mem::forget(refund);
+        }
+    }
+}
+
+/// Refunds the tokens of an [`acquire`](RateLimiter::acquire) dropped while waiting.
+struct Refund<'a> {
+    limiter: &'a RateLimiter,
+    bytes: usize,
+}
+
+impl Drop for Refund<'_> {
+    fn drop(&mut self) {
+        if let Some(bucket) = &self.limiter.requests {
+            bucket.lock().unwrap().refund(1.0);
+        }
+        if let Some(bucket) = &self.limiter.bytes {
+            bucket.lock().unwrap().refund(self.bytes as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bucket, RateLimiter};
+    use crate::RateLimit;
+    use futures_util::FutureExt;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_bucket() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(10.0, start);
+        for _ in 0..10 {
+            assert_eq!(Duration::ZERO, bucket.take(1.0, start));
+        }
+        assert_eq!(Duration::from_millis(100), bucket.take(1.0, start));
+        assert_eq!(Duration::from_millis(200), bucket.take(1.0, start));
+
+        // Refilled by 3 tokens, paying off the debt of 2.
+        let later = start + Duration::from_millis(300);
+        assert_eq!(Duration::ZERO, bucket.take(1.0, later));
+        assert_eq!(Duration::from_millis(100), bucket.take(1.0, later));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_acquire() {
+        let limiter = RateLimiter::new(RateLimit::requests_per_second(1));
+        limiter.acquire(0).await;
+        assert!(limiter.acquire(0).now_or_never().is_none());
+
+        // The cancelled request gave its token back, leaving the bucket out of debt.
+        let tokens = limiter.requests.as_ref().unwrap().lock().unwrap().tokens;
+        assert!(tokens > -0.5, "tokens: {}", tokens);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{APNClient, APNClientConfig, APNClientError};
+use std::collections::HashMap;
+
+/// One [`APNClient`] per app, for providers pushing to many apps from one process.
+///
+/// Each app keeps its own key, [default topic](APNClientConfig::with_topic) and endpoint, and
+/// its client caches its own provider token.
+#[derive(Default)]
+pub struct Registry {
+    clients: HashMap<String, APNClient>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `app_id`, replacing any client previously registered under it.
+    pub fn with_app(
+        mut self,
+        app_id: &str,
+        config: APNClientConfig,
+    ) -> Result<Self, APNClientError> {
+        self.clients
+            .insert(app_id.to_string(), APNClient::new(config)?);
+        Ok(self)
+    }
+
+    /// The client for `app_id`, or `None` if it was never registered.
+    pub fn client_for(&mut self, app_id: &str) -> Option<&mut APNClient> {
+        self.clients.get_mut(app_id)
+    }
+
+    pub fn app_ids(&self) -> impl Iterator<Item = &str> {
+        self.clients.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::MockServer;
+    use crate::{Payload, PushOption, Registry};
+
+    #[tokio::test]
+    async fn test_client_for() {
+        let chat = MockServer::start().await;
+        let shop = MockServer::start().await;
+        let mut registry = Registry::new()
+            .with_app(
+                "chat",
+                chat.config().unwrap().with_topic("com.example.chat"),
+            )
+            .unwrap()
+            .with_app(
+                "shop",
+                shop.config().unwrap().with_topic("com.example.shop"),
+            )
+            .unwrap();
+        assert!(registry.client_for("mail").is_none());
+
+        for app_id in ["chat", "shop", "shop"] {
+            let client = registry.client_for(app_id).unwrap();
+            client
+                .push(&Payload::default(), "device-token", PushOption::default())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(1, chat.requests().len());
+        let request = chat.last_request().unwrap();
+        assert_eq!(Some("com.example.chat"), request.header("apns-topic"));
+        assert_eq!(2, shop.requests().len());
+        let request = shop.last_request().unwrap();
+        assert_eq!(Some("com.example.shop"), request.header("apns-topic"));
+    }
+}
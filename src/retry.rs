@@ -0,0 +1,128 @@
+use crate::{APNClientError, ApnReason};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_BACKOFF: Duration = Duration::from_millis(100);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// How often, and how patiently, a push is retried after a retryable error.
+///
+/// Only errors for which [`APNClientError::is_retryable`] holds are retried, unless
+/// [`with_retry_after_send`](Self::with_retry_after_send) is set. Attempts are counted
+/// including the first one, and the delay between them doubles up to a maximum.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    reason_max_attempts: HashMap<ApnReason, u32>,
+    retry_after_send: bool,
+}
+
+impl RetryPolicy {
+    /// Makes at most `max_attempts` attempts for every error.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..Self::default()
+        }
+    }
+
+    /// Waits `base` before the first retry, doubling each time but never beyond `max`.
+    pub fn with_backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.backoff = base;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Overrides the number of attempts for errors APNs answered with `reason`.
+    pub fn with_reason_max_attempts(mut self, reason: ApnReason, max_attempts: u32) -> Self {
+        self.reason_max_attempts.insert(reason, max_attempts);
+        self
+    }
+
+    /// Also retries transport errors after the request was sent, such as a timeout waiting for
+    /// the answer. APNs may already have accepted the push, so this can deliver it twice. Off by
+    /// default.
+    pub fn with_retry_after_send(mut self, retry: bool) -> Self {
+        self.retry_after_send = retry;
+        self
+    }
+
+    /// The number of attempts allowed for a push failing with `error`.
+    pub fn max_attempts(&self, error: &APNClientError) -> u32 {
+        match error {
+            APNClientError::APNError { error, .. } => self
+                .reason_max_attempts
+                .get(&error.kind())
+                .copied()
+                .unwrap_or(self.max_attempts),
+            _ => self.max_attempts,
+        }
+    }
+
+    /// The delay before the next attempt after attempt number `attempt` failed with `error`, or
+    /// `None` if the push should not be retried.
+    pub fn next_delay(&self, attempt: u32, error: &APNClientError) -> Option<Duration> {
+        let retryable = error.is_retryable()
+            || (self.retry_after_send && matches!(error, APNClientError::HTTPError { .. }));
+        if !retryable || attempt >= self.max_attempts(error) {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        Some(self.backoff.saturating_mul(factor).min(self.max_backoff))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            backoff: DEFAULT_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            reason_max_attempts: HashMap::new(),
+            retry_after_send: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::apn_error;
+    use crate::{ApnReason, RetryPolicy};
+    use std::time::Duration;
+
+    #[test]
+    fn test_reason_max_attempts() {
+        let policy = RetryPolicy::new(5)
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(250))
+            .with_reason_max_attempts(ApnReason::InternalServerError, 2);
+
+        let too_many = apn_error(429, "TooManyRequests");
+        assert_eq!(5, policy.max_attempts(&too_many));
+        assert_eq!(
+            Some(Duration::from_millis(100)),
+            policy.next_delay(1, &too_many)
+        );
+        assert_eq!(
+            Some(Duration::from_millis(200)),
+            policy.next_delay(2, &too_many)
+        );
+        assert_eq!(
+            Some(Duration::from_millis(250)),
+            policy.next_delay(4, &too_many)
+        );
+        assert_eq!(None, policy.next_delay(5, &too_many));
+
+        let internal = apn_error(500, "InternalServerError");
+        assert_eq!(2, policy.max_attempts(&internal));
+        assert!(policy.next_delay(1, &internal).is_some());
+        assert_eq!(None, policy.next_delay(2, &internal));
+
+        assert_eq!(
+            None,
+            policy.next_delay(1, &apn_error(400, "BadDeviceToken"))
+        );
+    }
+}
@@ -1,5 +1,10 @@
-use serde::Serialize;
+use serde::de::Error as _;
+use serde::ser::Error;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::RawValue;
 use serde_json::{Map, Value};
+use serde_with::{DeserializeAs, SerializeAs};
 use snafu::{ResultExt, Snafu};
 
 #[derive(Snafu, Debug)]
@@ -23,3 +28,117 @@ impl<T: Serialize> TryFrom<StructWrapper<T>> for Map<String, Value> {
         }
     }
 }
+
+const DECIMAL_PRECISION: usize = 8;
+
+/// Serializes an `f64` in `0.0..=1.0` in plain decimal notation, rounded to eight places.
+///
+/// `serde_json` writes small floats such as `1e-7` in scientific notation, which some strict
+/// parsers reject. Values outside the unit range (or non-finite) fail to serialize.
+pub(crate) struct UnitDecimal;
+
+impl SerializeAs<f64> for UnitDecimal {
+    fn serialize_as<S: Serializer>(source: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if !(0.0..=1.0).contains(source) {
+            return Err(S::Error::custom(format!(
+                "{} is outside of the range 0.0 to 1.0",
+                source
+            )));
+        }
+
+        let mut decimal = format!("{:.*}", DECIMAL_PRECISION, source)
+            .trim_end_matches('0')
+            .to_string();
+        if decimal.ends_with('.') {
+            decimal.push('0');
+        }
+        RawValue::from_string(decimal)
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> DeserializeAs<'de, f64> for UnitDecimal {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        let value = f64::deserialize(deserializer)?;
+        if !(0.0..=1.0).contains(&value) {
+            return Err(D::Error::custom(format!(
+                "{} is outside of the range 0.0 to 1.0",
+                value
+            )));
+        }
+        Ok(value)
+    }
+}
+
+/// Serializes a JSON object with its keys sorted, at every level of nesting.
+///
+/// `serde_json` keeps insertion order when its `preserve_order` feature is enabled anywhere in the
+/// dependency graph, so sorting explicitly is what keeps equal payloads byte-identical.
+pub(crate) struct SortedKeys;
+
+impl SerializeAs<Map<String, Value>> for SortedKeys {
+    fn serialize_as<S: Serializer>(
+        source: &Map<String, Value>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serialize_sorted(source, serializer)
+    }
+}
+
+impl<'de> DeserializeAs<'de, Map<String, Value>> for SortedKeys {
+    fn deserialize_as<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Map<String, Value>, D::Error> {
+        Map::deserialize(deserializer)
+    }
+}
+
+struct Sorted<'a>(&'a Value);
+
+impl Serialize for Sorted<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Object(object) => serialize_sorted(object, serializer),
+            Value::Array(array) => serializer.collect_seq(array.iter().map(Sorted)),
+            value => value.serialize(serializer),
+        }
+    }
+}
+
+fn serialize_sorted<S: Serializer>(
+    object: &Map<String, Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(object.len()))?;
+    serialize_sorted_entries(object, &mut map)?;
+    map.end()
+}
+
+/// Writes the entries of `object` into an enclosing map, sorted like [`SortedKeys`].
+pub(crate) fn serialize_sorted_entries<M: SerializeMap>(
+    object: &Map<String, Value>,
+    map: &mut M,
+) -> Result<(), M::Error> {
+    let mut entries = object.iter().collect::<Vec<_>>();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+    for (key, value) in entries {
+        map.serialize_entry(key, &Sorted(value))?;
+    }
+    Ok(())
+}
+
+/// Removes `null` entries from `object` and every object nested within it, including objects
+/// inside arrays. Array elements themselves are kept so indices stay meaningful.
+pub(crate) fn prune_nulls(object: &mut Map<String, Value>) {
+    object.retain(|_, value| !value.is_null());
+    object.values_mut().for_each(prune_nested_nulls);
+}
+
+fn prune_nested_nulls(value: &mut Value) {
+    match value {
+        Value::Object(object) => prune_nulls(object),
+        Value::Array(array) => array.iter_mut().for_each(prune_nested_nulls),
+        _ => {}
+    }
+}
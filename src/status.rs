@@ -0,0 +1,333 @@
+use std::fmt::{Display, Formatter};
+
+/// The HTTP status codes documented for the APNs provider API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ApnStatus {
+    /// 200: the push was accepted.
+    Success,
+    /// 400: the request was malformed.
+    BadRequest,
+    /// 403: the certificate or provider token was rejected.
+    Forbidden,
+    /// 404: the request path was invalid.
+    NotFound,
+    /// 405: the request method was not `POST`.
+    MethodNotAllowed,
+    /// 410: the device token is no longer active for the topic.
+    Gone,
+    /// 413: the payload exceeded the size limit.
+    PayloadTooLarge,
+    /// 429: too many requests for the same device token, or too many token updates.
+    TooManyRequests,
+    /// 500: APNs failed internally.
+    InternalServerError,
+    /// 503: APNs is shutting down or unavailable.
+    ServiceUnavailable,
+    /// Any status Apple does not document.
+    Other(u16),
+}
+
+impl ApnStatus {
+    pub fn code(self) -> u16 {
+        match self {
+            Self::Success => 200,
+            Self::BadRequest => 400,
+            Self::Forbidden => 403,
+            Self::NotFound => 404,
+            Self::MethodNotAllowed => 405,
+            Self::Gone => 410,
+            Self::PayloadTooLarge => 413,
+            Self::TooManyRequests => 429,
+            Self::InternalServerError => 500,
+            Self::ServiceUnavailable => 503,
+            Self::Other(code) => code,
+        }
+    }
+
+    pub fn is_success(self) -> bool {
+        self == Self::Success
+    }
+}
+
+impl From<u16> for ApnStatus {
+    fn from(code: u16) -> Self {
+        match code {
+            200 => Self::Success,
+            400 => Self::BadRequest,
+            403 => Self::Forbidden,
+            404 => Self::NotFound,
+            405 => Self::MethodNotAllowed,
+            410 => Self::Gone,
+            413 => Self::PayloadTooLarge,
+            429 => Self::TooManyRequests,
+            500 => Self::InternalServerError,
+            503 => Self::ServiceUnavailable,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl From<ApnStatus> for u16 {
+    fn from(status: ApnStatus) -> Self {
+        status.code()
+    }
+}
+
+impl Display for ApnStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// The `reason` strings documented for APNs error responses.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ApnReason {
+    BadCollapseId,
+    BadDeviceToken,
+    BadExpirationDate,
+    BadMessageId,
+    BadPriority,
+    BadTopic,
+    DeviceTokenNotForTopic,
+    DuplicateHeaders,
+    IdleTimeout,
+    InvalidPushType,
+    MissingDeviceToken,
+    MissingTopic,
+    PayloadEmpty,
+    TopicDisallowed,
+    BadCertificate,
+    BadCertificateEnvironment,
+    ExpiredProviderToken,
+    Forbidden,
+    InvalidProviderToken,
+    MissingProviderToken,
+    UnrelatedKeyIdInToken,
+    BadPath,
+    MethodNotAllowed,
+    ExpiredToken,
+    Unregistered,
+    PayloadTooLarge,
+    TooManyProviderTokenUpdates,
+    TooManyRequests,
+    InternalServerError,
+    ServiceUnavailable,
+    Shutdown,
+    /// Any reason Apple does not document.
+    Other(String),
+}
+
+impl ApnReason {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::BadCollapseId => "BadCollapseId",
+            Self::BadDeviceToken => "BadDeviceToken",
+            Self::BadExpirationDate => "BadExpirationDate",
+            Self::BadMessageId => "BadMessageId",
+            Self::BadPriority => "BadPriority",
+            Self::BadTopic => "BadTopic",
+            Self::DeviceTokenNotForTopic => "DeviceTokenNotForTopic",
+            Self::DuplicateHeaders => "DuplicateHeaders",
+            Self::IdleTimeout => "IdleTimeout",
+            Self::InvalidPushType => "InvalidPushType",
+            Self::MissingDeviceToken => "MissingDeviceToken",
+            Self::MissingTopic => "MissingTopic",
+            Self::PayloadEmpty => "PayloadEmpty",
+            Self::TopicDisallowed => "TopicDisallowed",
+            Self::BadCertificate => "BadCertificate",
+            Self::BadCertificateEnvironment => "BadCertificateEnvironment",
+            Self::ExpiredProviderToken => "ExpiredProviderToken",
+            Self::Forbidden => "Forbidden",
+            Self::InvalidProviderToken => "InvalidProviderToken",
+            Self::MissingProviderToken => "MissingProviderToken",
+            Self::UnrelatedKeyIdInToken => "UnrelatedKeyIdInToken",
+            Self::BadPath => "BadPath",
+            Self::MethodNotAllowed => "MethodNotAllowed",
+            Self::ExpiredToken => "ExpiredToken",
+            Self::Unregistered => "Unregistered",
+            Self::PayloadTooLarge => "PayloadTooLarge",
+            Self::TooManyProviderTokenUpdates => "TooManyProviderTokenUpdates",
+            Self::TooManyRequests => "TooManyRequests",
+            Self::InternalServerError => "InternalServerError",
+            Self::ServiceUnavailable => "ServiceUnavailable",
+            Self::Shutdown => "Shutdown",
+            Self::Other(reason) => reason,
+        }
+    }
+
+    /// What Apple documents this reason to mean.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Self::BadCollapseId => "The apns-collapse-id header is longer than 64 bytes",
+            Self::BadDeviceToken => {
+                "The device token is malformed, or belongs to the other environment"
+            }
+            Self::BadExpirationDate => "The apns-expiration header is not a valid UNIX timestamp",
+            Self::BadMessageId => "The apns-id header is not a valid UUID",
+            Self::BadPriority => "The apns-priority header is not 1, 5 or 10",
+            Self::BadTopic => "The apns-topic header is invalid",
+            Self::DeviceTokenNotForTopic => "The device token does not belong to the topic",
+            Self::DuplicateHeaders => "A header was sent more than once",
+            Self::IdleTimeout => "The connection was idle for too long",
+            Self::InvalidPushType => "The apns-push-type header is not a known push type",
+            Self::MissingDeviceToken => "The request path has no device token",
+            Self::MissingTopic => "The apns-topic header is required but missing",
+            Self::PayloadEmpty => "The payload is empty",
+            Self::TopicDisallowed => "Pushing to this topic is not allowed",
+            Self::BadCertificate => "The certificate is invalid",
+            Self::BadCertificateEnvironment => "The certificate is for the other environment",
+            Self::ExpiredProviderToken => "The provider token is older than an hour",
+            Self::Forbidden => "The action is not allowed",
+            Self::InvalidProviderToken => {
+                "The provider token is invalid or its signature does not verify"
+            }
+            Self::MissingProviderToken => "No provider token or certificate was sent",
+            Self::UnrelatedKeyIdInToken => {
+                "The key id in the provider token does not belong to the team"
+            }
+            Self::BadPath => "The request path is invalid",
+            Self::MethodNotAllowed => "The request method is not POST",
+            Self::ExpiredToken => "The device token has expired",
+            Self::Unregistered => "The device token is no longer active for the topic",
+            Self::PayloadTooLarge => "The payload exceeds the size limit",
+            Self::TooManyProviderTokenUpdates => "The provider token was refreshed too often",
+            Self::TooManyRequests => "Too many pushes were sent to the same device token",
+            Self::InternalServerError => "APNs failed internally",
+            Self::ServiceUnavailable => "APNs is unavailable",
+            Self::Shutdown => "The APNs server is shutting down",
+            Self::Other(_) => "The reason is not documented",
+        }
+    }
+
+    /// What to do about a push that failed for this reason.
+    pub fn remediation(&self) -> &'static str {
+        match self {
+            Self::BadCollapseId => "Shorten the collapse id to at most 64 bytes",
+            Self::BadDeviceToken => "Check the token and its environment; otherwise delete it",
+            Self::BadExpirationDate => "Send the expiration as whole seconds since the epoch",
+            Self::BadMessageId => "Send the apns-id as a UUID in its 8-4-4-4-12 form",
+            Self::BadPriority => "Use priority 10, 5 or 1",
+            Self::BadTopic => "Use the app's bundle id, with the suffix matching the push type",
+            Self::DeviceTokenNotForTopic => "Send to the bundle id the token was registered for",
+            Self::DuplicateHeaders => "Send each apns-* header at most once",
+            Self::IdleTimeout => "Nothing; the connection is reopened for the next push",
+            Self::InvalidPushType => "Use one of the documented push types",
+            Self::MissingDeviceToken => "Pass a non-empty device token",
+            Self::MissingTopic => "Set a topic on the push option or the client",
+            Self::PayloadEmpty => "Send a payload with at least an aps dictionary",
+            Self::TopicDisallowed => {
+                "Check the topic against the capabilities of the key or certificate"
+            }
+            Self::BadCertificate => "Use a valid APNs certificate",
+            Self::BadCertificateEnvironment => "Use a certificate for this endpoint's environment",
+            Self::ExpiredProviderToken => {
+                "Check the system clock; tokens are re-signed before they expire"
+            }
+            Self::Forbidden => "Check the key's permissions in the developer account",
+            Self::InvalidProviderToken => {
+                "Check that the key id, team id and .p8 key belong together"
+            }
+            Self::MissingProviderToken => "Send a provider token in the authorization header",
+            Self::UnrelatedKeyIdInToken => "Check the key id against the developer account",
+            Self::BadPath => "Check the device token and endpoint",
+            Self::MethodNotAllowed => "Send pushes with POST",
+            Self::ExpiredToken => "Delete this device token",
+            Self::Unregistered => "Delete this device token",
+            Self::PayloadTooLarge => "Shrink the payload below 4 KB, or 5 KB for VoIP",
+            Self::TooManyProviderTokenUpdates => "Reuse the provider token for at least 20 minutes",
+            Self::TooManyRequests => "Back off before pushing to this device again",
+            Self::InternalServerError => "Retry later",
+            Self::ServiceUnavailable => "Retry later, possibly on another connection",
+            Self::Shutdown => "Retry on a new connection",
+            Self::Other(_) => "Check Apple's documentation for the reason",
+        }
+    }
+}
+
+impl From<&str> for ApnReason {
+    fn from(reason: &str) -> Self {
+        match reason {
+            "BadCollapseId" => Self::BadCollapseId,
+            "BadDeviceToken" => Self::BadDeviceToken,
+            "BadExpirationDate" => Self::BadExpirationDate,
+            "BadMessageId" => Self::BadMessageId,
+            "BadPriority" => Self::BadPriority,
+            "BadTopic" => Self::BadTopic,
+            "DeviceTokenNotForTopic" => Self::DeviceTokenNotForTopic,
+            "DuplicateHeaders" => Self::DuplicateHeaders,
+            "IdleTimeout" => Self::IdleTimeout,
+            "InvalidPushType" => Self::InvalidPushType,
+            "MissingDeviceToken" => Self::MissingDeviceToken,
+            "MissingTopic" => Self::MissingTopic,
+            "PayloadEmpty" => Self::PayloadEmpty,
+            "TopicDisallowed" => Self::TopicDisallowed,
+            "BadCertificate" => Self::BadCertificate,
+            "BadCertificateEnvironment" => Self::BadCertificateEnvironment,
+            "ExpiredProviderToken" => Self::ExpiredProviderToken,
+            "Forbidden" => Self::Forbidden,
+            "InvalidProviderToken" => Self::InvalidProviderToken,
+            "MissingProviderToken" => Self::MissingProviderToken,
+            "UnrelatedKeyIdInToken" => Self::UnrelatedKeyIdInToken,
+            "BadPath" => Self::BadPath,
+            "MethodNotAllowed" => Self::MethodNotAllowed,
+            "ExpiredToken" => Self::ExpiredToken,
+            "Unregistered" => Self::Unregistered,
+            "PayloadTooLarge" => Self::PayloadTooLarge,
+            "TooManyProviderTokenUpdates" => Self::TooManyProviderTokenUpdates,
+            "TooManyRequests" => Self::TooManyRequests,
+            "InternalServerError" => Self::InternalServerError,
+            "ServiceUnavailable" => Self::ServiceUnavailable,
+            "Shutdown" => Self::Shutdown,
+            reason => Self::Other(reason.to_string()),
+        }
+    }
+}
+
+impl Display for ApnReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ApnReason, ApnStatus};
+
+    #[test]
+    fn test_status_from_code() {
+        let documented = [200, 400, 403, 404, 405, 410, 413, 429, 500, 503];
+        for code in documented {
+            let status = ApnStatus::from(code);
+            assert!(!matches!(status, ApnStatus::Other(_)), "{}", code);
+            assert_eq!(code, u16::from(status));
+        }
+        assert_eq!(ApnStatus::Gone, ApnStatus::from(410));
+        assert_eq!(ApnStatus::Other(502), ApnStatus::from(502));
+        assert_eq!(502, ApnStatus::Other(502).code());
+        assert!(ApnStatus::from(200).is_success());
+    }
+
+    #[test]
+    fn test_reason_round_trip() {
+        assert_eq!(ApnReason::Unregistered, ApnReason::from("Unregistered"));
+        assert_eq!("TooManyRequests", ApnReason::TooManyRequests.as_str());
+        assert_eq!(
+            ApnReason::Other("SomethingNew".to_string()),
+            ApnReason::from("SomethingNew")
+        );
+        assert_eq!("SomethingNew", ApnReason::from("SomethingNew").to_string());
+    }
+
+    #[test]
+    fn test_reason_remediation() {
+        assert_eq!(
+            "Delete this device token",
+            ApnReason::Unregistered.remediation()
+        );
+        assert!(!ApnReason::BadTopic.explanation().is_empty());
+        let other = ApnReason::from("SomethingNew");
+        assert_eq!("The reason is not documented", other.explanation());
+    }
+}
@@ -1,17 +1,89 @@
-use crate::serialize::{JsonObjectError, StructWrapper};
-use reqwest::header::HeaderMap;
-use serde::Serialize;
+use crate::serialize::{
+    JsonObjectError, SortedKeys, StructWrapper, UnitDecimal, prune_nulls, serialize_sorted_entries,
+};
+use crate::{Clock, RetryPolicy, SystemClock};
+use bytes::Bytes;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{Map, Value};
-use snafu::{ResultExt, Snafu};
-use serde_with::{serde_as, BoolFromInt};
+use serde_with::{BoolFromInt, serde_as};
+use snafu::{OptionExt, ResultExt, Snafu, ensure};
+use std::time::{Duration, SystemTimeError, UNIX_EPOCH};
+#[cfg(feature = "uuid")]
+use uuid::Uuid;
 
 #[derive(Snafu, Debug)]
 #[non_exhaustive]
 pub enum BuildError {
-    ConvertJsonObjectError { source: JsonObjectError },
+    ConvertJsonObjectError {
+        source: JsonObjectError,
+    },
+    #[snafu(display(
+        "Alert must have a non-empty body, or at least one of title, subtitle and body"
+    ))]
+    EmptyAlertError,
+    #[snafu(display("Live Activity attributes and attributes-type must be set together"))]
+    IncompleteAttributesError,
+    #[snafu(display("Sound volume is only allowed on a critical sound"))]
+    InvalidSoundError,
+    #[snafu(display("Thread id must not be empty"))]
+    EmptyThreadIdError,
+    #[snafu(display("{} must not be set together with {}", field, other))]
+    ConflictingFieldsError {
+        field: &'static str,
+        other: &'static str,
+    },
+    #[snafu(display("MDM push magic must not be empty"))]
+    EmptyPushMagicError,
+    #[snafu(display("Payload must be a JSON object"))]
+    NotAnObjectError,
+    #[snafu(display("Payload must have an aps dictionary"))]
+    MissingApsError,
+    #[snafu(display("aps key `{}` is not supported", key))]
+    UnsupportedApsKeyError {
+        key: String,
+    },
+    #[snafu(display("Unable to serialize: {}", source))]
+    SerializeError {
+        source: serde_json::Error,
+    },
+    #[snafu(display("Unable to deserialize: {}", source))]
+    DeserializeError {
+        source: serde_json::Error,
+    },
+    #[snafu(display("Unable to compress or decompress payload: {}", source))]
+    CompressionError {
+        source: std::io::Error,
+    },
+}
+
+#[derive(Snafu, Debug)]
+#[non_exhaustive]
+pub enum PushOptionError {
+    #[snafu(display("Invalid value for header {}", name))]
+    InvalidHeaderError { name: &'static str },
+    #[snafu(display("Topic {} is not valid for push type {}", topic, push_type.as_str()))]
+    TopicMismatchError { topic: String, push_type: PushType },
+    #[snafu(display("Priority {} is not allowed for push type {}", priority, push_type.as_str()))]
+    PriorityNotAllowedError { priority: u8, push_type: PushType },
+}
+
+#[derive(Snafu, Debug)]
+#[non_exhaustive]
+pub enum ParseError {
+    #[snafu(display("Payload is not a JSON object: {}", source))]
+    MalformedJsonError { source: serde_json::Error },
+    #[snafu(display("Unknown aps key `{}`", key))]
+    UnknownApsKeyError { key: String },
+    #[snafu(display("Payload has no aps dictionary"))]
+    MissingApsDictionaryError,
+    #[snafu(display("Invalid aps dictionary: {}", source))]
+    InvalidApsError { source: serde_json::Error },
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Title {
     #[serde(rename = "title")]
     Normal(String),
@@ -25,7 +97,30 @@ pub enum Title {
     },
 }
 
-#[derive(Serialize, Debug)]
+impl Title {
+    /// The text itself, or the localization key for a localized title.
+    pub fn text(&self) -> &str {
+        match self {
+            Title::Normal(text) => text,
+            Title::Localized { key, .. } => key,
+        }
+    }
+
+    /// The localization arguments, empty unless the title is localized.
+    pub fn args(&self) -> &[String] {
+        match self {
+            Title::Normal(_) => &[],
+            Title::Localized { args, .. } => args.as_deref().unwrap_or_default(),
+        }
+    }
+
+    pub fn is_localized(&self) -> bool {
+        matches!(self, Title::Localized { .. })
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Subtitle {
     #[serde(rename = "subtitle")]
     Normal(String),
@@ -39,7 +134,30 @@ pub enum Subtitle {
     },
 }
 
-#[derive(Serialize, Debug)]
+impl Subtitle {
+    /// The text itself, or the localization key for a localized subtitle.
+    pub fn text(&self) -> &str {
+        match self {
+            Subtitle::Normal(text) => text,
+            Subtitle::Localized { key, .. } => key,
+        }
+    }
+
+    /// The localization arguments, empty unless the subtitle is localized.
+    pub fn args(&self) -> &[String] {
+        match self {
+            Subtitle::Normal(_) => &[],
+            Subtitle::Localized { args, .. } => args.as_deref().unwrap_or_default(),
+        }
+    }
+
+    pub fn is_localized(&self) -> bool {
+        matches!(self, Subtitle::Localized { .. })
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Body {
     #[serde(rename = "body")]
     Normal(String),
@@ -53,10 +171,35 @@ pub enum Body {
     },
 }
 
-#[derive(Serialize, Debug)]
+impl Body {
+    /// The text itself, or the localization key for a localized body.
+    pub fn text(&self) -> &str {
+        match self {
+            Body::Normal(text) => text,
+            Body::Localized { key, .. } => key,
+        }
+    }
+
+    /// The localization arguments, empty unless the body is localized.
+    pub fn args(&self) -> &[String] {
+        match self {
+            Body::Normal(_) => &[],
+            Body::Localized { args, .. } => args.as_deref().unwrap_or_default(),
+        }
+    }
+
+    pub fn is_localized(&self) -> bool {
+        matches!(self, Body::Localized { .. })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[non_exhaustive]
 #[serde(untagged)]
+#[serde(from = "AlertRepr")]
 pub enum Alert {
     Body(String),
+    /// Also built with [`Alert::full`] and the `with_*` methods.
     Full {
         #[serde(flatten)]
         title: Option<Title>,
@@ -67,11 +210,206 @@ pub enum Alert {
         #[serde(skip_serializing_if = "Option::is_none")]
         #[serde(rename = "launch-image")]
         launch_image: Option<String>,
+        /// The text inserted into the summary of a group of notifications, such as a sender.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "summary-arg")]
+        summary_arg: Option<String>,
+        /// How many items this notification adds to the group summary, `1` if unset.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde(rename = "summary-arg-count")]
+        summary_arg_count: Option<u32>,
     },
 }
 
+/// The wire form of an alert, flattened, since `serde` cannot deserialize the flattened
+/// optional enums of [`Alert::Full`] directly.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AlertRepr {
+    Body(String),
+    Full(Box<FullAlertRepr>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct FullAlertRepr {
+    title: Option<String>,
+    title_loc_key: Option<String>,
+    title_loc_args: Option<Vec<String>>,
+    subtitle: Option<String>,
+    subtitle_loc_key: Option<String>,
+    subtitle_loc_args: Option<Vec<String>>,
+    body: Option<String>,
+    loc_key: Option<String>,
+    loc_args: Option<Vec<String>>,
+    launch_image: Option<String>,
+    summary_arg: Option<String>,
+    summary_arg_count: Option<u32>,
+}
+
+impl From<AlertRepr> for Alert {
+    fn from(repr: AlertRepr) -> Self {
+        let alert = match repr {
+            AlertRepr::Body(body) => return Alert::Body(body),
+            AlertRepr::Full(alert) => *alert,
+        };
+        Alert::Full {
+            title: match (alert.title, alert.title_loc_key) {
+                (Some(title), _) => Some(Title::Normal(title)),
+                (None, Some(key)) => Some(Title::Localized {
+                    key,
+                    args: alert.title_loc_args,
+                }),
+                (None, None) => None,
+            },
+            subtitle: match (alert.subtitle, alert.subtitle_loc_key) {
+                (Some(subtitle), _) => Some(Subtitle::Normal(subtitle)),
+                (None, Some(key)) => Some(Subtitle::Localized {
+                    key,
+                    args: alert.subtitle_loc_args,
+                }),
+                (None, None) => None,
+            },
+            body: match (alert.body, alert.loc_key) {
+                (Some(body), _) => Some(Body::Normal(body)),
+                (None, Some(key)) => Some(Body::Localized {
+                    key,
+                    args: alert.loc_args,
+                }),
+                (None, None) => None,
+            },
+            launch_image: alert.launch_image,
+            summary_arg: alert.summary_arg,
+            summary_arg_count: alert.summary_arg_count,
+        }
+    }
+}
+
+impl Alert {
+    /// The title text or localization key, if any.
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            Alert::Body(_) => None,
+            Alert::Full { title, .. } => title.as_ref().map(Title::text),
+        }
+    }
+
+    /// The subtitle text or localization key, if any.
+    pub fn subtitle(&self) -> Option<&str> {
+        match self {
+            Alert::Body(_) => None,
+            Alert::Full { subtitle, .. } => subtitle.as_ref().map(Subtitle::text),
+        }
+    }
+
+    /// The body text or localization key, including the body of a string-only alert.
+    pub fn body(&self) -> Option<&str> {
+        match self {
+            Alert::Body(body) => Some(body),
+            Alert::Full { body, .. } => body.as_ref().map(Body::text),
+        }
+    }
+
+    /// A full alert with nothing set yet.
+    pub fn full() -> Self {
+        Alert::Full {
+            title: None,
+            subtitle: None,
+            body: None,
+            launch_image: None,
+            summary_arg: None,
+            summary_arg_count: None,
+        }
+    }
+
+    /// Sets the title, turning a string-only alert into a full one.
+    pub fn with_title(self, title: Title) -> Self {
+        let mut alert = self.into_full();
+        if let Alert::Full { title: field, .. } = &mut alert {
+            *field = Some(title);
+        }
+        alert
+    }
+
+    /// Sets the subtitle, turning a string-only alert into a full one.
+    pub fn with_subtitle(self, subtitle: Subtitle) -> Self {
+        let mut alert = self.into_full();
+        if let Alert::Full {
+            subtitle: field, ..
+        } = &mut alert
+        {
+            *field = Some(subtitle);
+        }
+        alert
+    }
+
+    /// Sets the body, turning a string-only alert into a full one.
+    pub fn with_body(self, body: Body) -> Self {
+        let mut alert = self.into_full();
+        if let Alert::Full { body: field, .. } = &mut alert {
+            *field = Some(body);
+        }
+        alert
+    }
+
+    /// Sets the `launch-image`, turning a string-only alert into a full one.
+    pub fn with_launch_image(self, image: impl Into<String>) -> Self {
+        let mut alert = self.into_full();
+        if let Alert::Full { launch_image, .. } = &mut alert {
+            *launch_image = Some(image.into());
+        }
+        alert
+    }
+
+    /// Sets the `summary-arg`, turning a string-only alert into a full one.
+    pub fn with_summary_arg(self, arg: impl Into<String>) -> Self {
+        let mut alert = self.into_full();
+        if let Alert::Full { summary_arg, .. } = &mut alert {
+            *summary_arg = Some(arg.into());
+        }
+        alert
+    }
+
+    /// Sets the `summary-arg-count`, turning a string-only alert into a full one.
+    pub fn with_summary_arg_count(self, count: u32) -> Self {
+        let mut alert = self.into_full();
+        if let Alert::Full {
+            summary_arg_count, ..
+        } = &mut alert
+        {
+            *summary_arg_count = Some(count);
+        }
+        alert
+    }
+
+    fn into_full(self) -> Self {
+        match self {
+            Alert::Body(body) => Alert::full().with_body(Body::Normal(body)),
+            alert => alert,
+        }
+    }
+
+    /// Rejects alerts that would serialize to an empty string or an empty object.
+    pub fn validate(&self) -> Result<(), BuildError> {
+        let empty = match self {
+            Alert::Body(body) => body.is_empty(),
+            Alert::Full {
+                title,
+                subtitle,
+                body,
+                ..
+            } => title.is_none() && subtitle.is_none() && body.is_none(),
+        };
+        if empty {
+            return Err(BuildError::EmptyAlertError);
+        }
+        Ok(())
+    }
+}
+
 #[serde_as]
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[non_exhaustive]
 #[serde(untagged)]
 pub enum Sound {
     Regular(String),
@@ -82,11 +420,37 @@ pub enum Sound {
         #[serde(skip_serializing_if = "Option::is_none")]
         name: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
+        #[serde_as(as = "Option<UnitDecimal>")]
         volume: Option<f64>,
     },
 }
 
-#[derive(Serialize, Debug)]
+impl Sound {
+    /// A critical alert sound, played at `volume` between `0` and `1`.
+    pub fn critical(name: impl Into<String>, volume: f64) -> Self {
+        Sound::Critical {
+            critical: Some(true),
+            name: Some(name.into()),
+            volume: Some(volume),
+        }
+    }
+
+    /// Checks that a `volume` is only set together with `critical`, since only critical alerts
+    /// play at a volume of their own.
+    pub fn validate(&self) -> Result<(), BuildError> {
+        match self {
+            Sound::Critical {
+                critical,
+                volume: Some(_),
+                ..
+            } if *critical != Some(true) => InvalidSoundSnafu.fail(),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[non_exhaustive]
 #[serde(rename_all = "kebab-case")]
 pub enum InterruptionLevel {
     Passive,
@@ -95,8 +459,75 @@ pub enum InterruptionLevel {
     Critical,
 }
 
+/// When an ended Live Activity leaves the Lock Screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DismissalPolicy {
+    /// Let the system decide, keeping it on the Lock Screen for up to four hours.
+    Default,
+    /// Remove it right away.
+    Immediate,
+    /// Remove it at the given UNIX timestamp, at most four hours after it ended.
+    At(u64),
+}
+
+impl DismissalPolicy {
+    /// The `dismissal-date` this policy is sent as.
+    ///
+    /// Any date in the past dismisses immediately, so `Immediate` is sent as the UNIX epoch.
+    pub fn dismissal_date(self) -> Option<u64> {
+        match self {
+            Self::Default => None,
+            Self::Immediate => Some(0),
+            Self::At(date) => Some(date),
+        }
+    }
+}
+
+/// Every key of the `aps` dictionary, as serialized from [`Notification`].
+const APS_KEYS: &[&str] = &[
+    "alert",
+    "badge",
+    "sound",
+    "thread-id",
+    "category",
+    "content-available",
+    "mutable-content",
+    "target-content-id",
+    "interruption-level",
+    "relevance-score",
+    "filter-criteria",
+    "stale-date",
+    "content-state",
+    "timestamp",
+    "event",
+    "dismissal-date",
+    "attributes-type",
+    "attributes",
+    "input-push-token",
+    "input-push-channel",
+];
+
+/// Below this, a relevance score on a critical notification likely expects it to be demoted.
+const LOW_RELEVANCE_SCORE: f64 = 0.5;
+
+/// Every key of a dictionary `alert`, as read into [`Alert::Full`].
+const ALERT_KEYS: &[&str] = &[
+    "title",
+    "title-loc-key",
+    "title-loc-args",
+    "subtitle",
+    "subtitle-loc-key",
+    "subtitle-loc-args",
+    "body",
+    "loc-key",
+    "loc-args",
+    "launch-image",
+    "summary-arg",
+    "summary-arg-count",
+];
+
 #[serde_as]
-#[derive(Serialize, Default, Debug)]
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct Notification {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -119,13 +550,18 @@ pub struct Notification {
     pub target_content_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub interruption_level: Option<InterruptionLevel>,
+    /// Ranks the notification within the notification summary, from `0.0` to `1.0`.
+    ///
+    /// It has no effect on `critical` notifications, which always break through on their own.
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<UnitDecimal>")]
     pub relevance_score: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub filter_criteria: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stale_date: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<SortedKeys>")]
     pub content_state: Option<Map<String, Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timestamp: Option<u64>,
@@ -136,10 +572,38 @@ pub struct Notification {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub attributes_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<SortedKeys>")]
     pub attributes: Option<Map<String, Value>>,
+    /// Asks the Live Activity started by this push for a token to send it updates with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<BoolFromInt>")]
+    pub input_push_token: Option<bool>,
+    /// The broadcast channel the Live Activity started by this push subscribes to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_push_channel: Option<String>,
 }
 
 impl Notification {
+    /// A silent background update: `content-available` set and nothing user-visible.
+    ///
+    /// Send it with [`PushOption::background`].
+    pub fn silent() -> Self {
+        let notification = Self {
+            content_available: Some(true),
+            ..Self::default()
+        };
+        debug_assert!(notification.is_silent());
+        notification
+    }
+
+    /// Whether this is a background update with no alert, sound or badge.
+    pub fn is_silent(&self) -> bool {
+        self.content_available == Some(true)
+            && self.alert.is_none()
+            && self.sound.is_none()
+            && self.badge.is_none()
+    }
+
     pub fn with_content_state<T: Serialize>(mut self, state: T) -> Result<Self, BuildError> {
         self.content_state = Some(
             StructWrapper(state)
@@ -149,6 +613,22 @@ impl Notification {
         Ok(self)
     }
 
+    /// Like [`with_content_state`](Self::with_content_state), for state built up key by key
+    /// rather than from a struct, e.g. `[("progress", json!(0.4)), ("eta", json!("12:30"))]`.
+    pub fn with_content_state_entries<I, K>(mut self, entries: I) -> Self
+    where
+        I: IntoIterator<Item = (K, Value)>,
+        K: Into<String>,
+    {
+        self.content_state = Some(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+        );
+        self
+    }
+
     pub fn with_attributes<T: Serialize>(mut self, attributes: T) -> Result<Self, BuildError> {
         self.attributes = Some(
             StructWrapper(attributes)
@@ -157,35 +637,479 @@ impl Notification {
         );
         Ok(self)
     }
+
+    /// Sets the attributes starting a Live Activity together with `attributes-type`, the name of
+    /// the app's `ActivityAttributes` struct they decode into. APNs fails a start missing either.
+    pub fn with_activity_attributes<T: Serialize>(
+        self,
+        type_name: impl Into<String>,
+        attributes: T,
+    ) -> Result<Self, BuildError> {
+        let type_name = type_name.into();
+        ensure!(!type_name.is_empty(), IncompleteAttributesSnafu);
+        Ok(Self {
+            attributes_type: Some(type_name),
+            ..self.with_attributes(attributes)?
+        })
+    }
+
+    /// A Live Activity `update` event carrying `state` as its content-state.
+    ///
+    /// APNs replaces the whole content-state on the device, so `state` is always sent in full
+    /// rather than as a delta. `previous` is only compared against to warn about updates that
+    /// change nothing, which still count against the Live Activity update budget.
+    pub fn live_activity_update<T: Serialize>(
+        previous: &T,
+        state: &T,
+        timestamp: u64,
+    ) -> Result<Self, BuildError> {
+        let previous: Map<String, Value> = StructWrapper(previous)
+            .try_into()
+            .context(ConvertJsonObjectSnafu)?;
+        let notification = Self {
+            event: Some("update".to_string()),
+            timestamp: Some(timestamp),
+            ..Self::default()
+        }
+        .with_content_state(state)?;
+        if notification.content_state.as_ref() == Some(&previous) {
+            tracing::warn!(
+                timestamp,
+                "Live Activity update does not change the content-state"
+            );
+        }
+        Ok(notification)
+    }
+
+    /// Like [`live_activity_update`](Self::live_activity_update), but the content-state only
+    /// carries the top-level keys whose value differs from `previous`, keeping updates small.
+    ///
+    /// APNs still hands the partial state to the app as the whole content-state, so this only
+    /// works if the app's `ContentState` decodes every field as optional and merges the update
+    /// into the state it has. Keys missing from `state` are sent as `null`.
+    pub fn live_activity_update_diff<T: Serialize>(
+        previous: &T,
+        state: &T,
+        timestamp: u64,
+    ) -> Result<Self, BuildError> {
+        let mut notification = Self::live_activity_update(previous, state, timestamp)?;
+        let previous: Map<String, Value> = StructWrapper(previous)
+            .try_into()
+            .context(ConvertJsonObjectSnafu)?;
+        if let Some(state) = notification.content_state.take() {
+            let mut diff = previous
+                .keys()
+                .filter(|key| !state.contains_key(*key))
+                .map(|key| (key.clone(), Value::Null))
+                .collect::<Map<_, _>>();
+            diff.extend(
+                state
+                    .into_iter()
+                    .filter(|(key, value)| previous.get(key) != Some(value)),
+            );
+            notification.content_state = Some(diff);
+        }
+        Ok(notification)
+    }
+
+    /// A Live Activity `end` event with its final `state`, dismissed according to `policy`.
+    pub fn live_activity_end<T: Serialize>(
+        state: &T,
+        timestamp: u64,
+        policy: DismissalPolicy,
+    ) -> Result<Self, BuildError> {
+        Self {
+            event: Some("end".to_string()),
+            timestamp: Some(timestamp),
+            dismissal_date: policy.dismissal_date(),
+            ..Self::default()
+        }
+        .with_content_state(state)
+    }
+
+    /// Sets the `thread-id`, trimmed of surrounding whitespace.
+    ///
+    /// Notifications with identical thread ids are grouped together on the device, so reuse the
+    /// same id for every notification about the same conversation or subject.
+    pub fn with_thread_id(mut self, thread_id: impl Into<String>) -> Result<Self, BuildError> {
+        let thread_id = thread_id.into();
+        let thread_id = thread_id.trim();
+        ensure!(!thread_id.is_empty(), EmptyThreadIdSnafu);
+        self.thread_id = Some(thread_id.to_string());
+        Ok(self)
+    }
+
+    /// The notification as JSON, for embedding into a larger structure.
+    pub fn to_value(&self) -> Result<Value, BuildError> {
+        serde_json::to_value(self).context(SerializeSnafu)
+    }
+
+    /// Parses the `aps` dictionary of a complete payload received from elsewhere, as
+    /// [`StrictPayload::parse`] does, and drops the custom data.
+    ///
+    /// A payload without `aps` fails with [`ParseError::MissingApsDictionaryError`].
+    pub fn parse_strict(json: &str) -> Result<Self, ParseError> {
+        let object: Map<String, Value> = serde_json::from_str(json).context(MalformedJsonSnafu)?;
+        ensure!(object.contains_key("aps"), MissingApsDictionarySnafu);
+        StrictPayload::from_map(object).map(|StrictPayload(payload)| payload.aps)
+    }
+
+    pub fn validate(&self) -> Result<(), BuildError> {
+        if let Some(alert) = &self.alert {
+            alert.validate()?;
+        }
+        if let Some(sound) = &self.sound {
+            sound.validate()?;
+        }
+        // An alert or sound turns a background update into a visible notification.
+        if self.content_available == Some(true) {
+            ensure!(
+                self.alert.is_none(),
+                ConflictingFieldsSnafu {
+                    field: "content-available",
+                    other: "alert",
+                }
+            );
+            ensure!(
+                self.sound.is_none(),
+                ConflictingFieldsSnafu {
+                    field: "content-available",
+                    other: "sound",
+                }
+            );
+        }
+        ensure!(
+            self.attributes.is_some() == self.attributes_type.is_some(),
+            IncompleteAttributesSnafu
+        );
+        if let (Some(InterruptionLevel::Critical), Some(relevance_score)) =
+            (&self.interruption_level, self.relevance_score)
+            && relevance_score < LOW_RELEVANCE_SCORE
+        {
+            tracing::warn!(
+                relevance_score,
+                "a low relevance-score does not demote critical notifications"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The first key of `aps` APNs does not know, with keys of a dictionary `alert` given as
+/// `alert.<key>`.
+fn unknown_aps_key(aps: &Value) -> Option<String> {
+    let Value::Object(aps) = aps else {
+        return None;
+    };
+    if let Some(key) = aps.keys().find(|key| !APS_KEYS.contains(&key.as_str())) {
+        return Some(key.clone());
+    }
+    let Some(Value::Object(alert)) = aps.get("alert") else {
+        return None;
+    };
+    alert
+        .keys()
+        .find(|key| !ALERT_KEYS.contains(&key.as_str()))
+        .map(|key| format!("alert.{}", key))
 }
 
-#[derive(Serialize, Default, Debug)]
+const DEFAULT_MEDIA_URL_KEY: &str = "media-url";
+const BADGE_INCREMENT_KEY: &str = "badge-increment";
+const MDM_KEY: &str = "mdm";
+
+#[derive(Default, Debug)]
 pub struct Payload {
     pub aps: Notification,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(flatten)]
     pub custom: Option<Map<String, Value>>,
 }
 
+/// A [`Payload`] serialized without its `aps` dictionary while that is empty, sending only the
+/// custom data. See [`Payload::omit_empty_aps`].
+#[derive(Debug, Clone, Copy)]
+pub struct OmitEmptyAps<'a>(pub &'a Payload);
+
+impl Serialize for OmitEmptyAps<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let include_aps = self.0.aps != Notification::default();
+        self.0.serialize_entries(serializer, include_aps)
+    }
+}
+
+impl OmitEmptyAps<'_> {
+    /// The serialized payload, to send with
+    /// [`APNClient::push_bytes`](crate::APNClient::push_bytes).
+    pub fn to_bytes(&self) -> Result<Bytes, BuildError> {
+        serde_json::to_vec(self)
+            .map(Bytes::from)
+            .context(SerializeSnafu)
+    }
+}
+
+/// The serialized size of a [`Payload`], split by part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeBreakdown {
+    /// The `aps` key and dictionary.
+    pub aps_bytes: usize,
+    /// Everything else: the custom data, plus the braces and commas of the outer object.
+    pub custom_bytes: usize,
+    pub total: usize,
+}
+
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_entries(serializer, self.includes_aps())
+    }
+}
+
+impl<'de> Deserialize<'de> for Payload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::from_map(Map::deserialize(deserializer)?)
+    }
+}
+
+/// A [`Payload`] deserialized strictly: unknown keys in the `aps` dictionary or its `alert`,
+/// such as a misspelled `mutable-contents`, are rejected. Custom keys outside `aps` are
+/// app-defined and always allowed.
+#[derive(Debug, Default)]
+pub struct StrictPayload(pub Payload);
+
+impl StrictPayload {
+    /// Parses a complete payload received from elsewhere, telling which check failed.
+    pub fn parse(json: &str) -> Result<Self, ParseError> {
+        Self::from_map(serde_json::from_str(json).context(MalformedJsonSnafu)?)
+    }
+
+    fn from_map(mut custom: Map<String, Value>) -> Result<Self, ParseError> {
+        let aps = match custom.remove("aps") {
+            Some(aps) => {
+                if let Some(key) = unknown_aps_key(&aps) {
+                    return UnknownApsKeySnafu { key }.fail();
+                }
+                Some(serde_json::from_value(aps).context(InvalidApsSnafu)?)
+            }
+            None => None,
+        };
+        Ok(Self(Payload::from_parts(aps, custom)))
+    }
+}
+
+impl<'de> Deserialize<'de> for StrictPayload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::from_map(Map::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<Notification> for Payload {
+    fn from(aps: Notification) -> Self {
+        Self {
+            aps,
+            ..Self::default()
+        }
+    }
+}
+
 impl Payload {
-    pub fn with_custom<T: Serialize>(mut self, custom: T) -> Result<Self, BuildError> {
-        self.custom = Some(
-            StructWrapper(custom)
-                .try_into()
-                .context(ConvertJsonObjectSnafu)?,
+    fn from_map<E: serde::de::Error>(mut custom: Map<String, Value>) -> Result<Self, E> {
+        let aps = match custom.remove("aps") {
+            Some(aps) => Some(serde_json::from_value(aps).map_err(E::custom)?),
+            None => None,
+        };
+        Ok(Self::from_parts(aps, custom))
+    }
+
+    fn from_parts(aps: Option<Notification>, custom: Map<String, Value>) -> Self {
+        Self {
+            aps: aps.unwrap_or_default(),
+            custom: (!custom.is_empty()).then_some(custom),
+        }
+    }
+
+    /// Parses a complete payload, such as one rendered from a template.
+    ///
+    /// The `aps` dictionary becomes the typed [`aps`](Self::aps), so it is validated and can
+    /// still be adjusted with [`aps_mut`](Self::aps_mut); a key it does not know fails with
+    /// [`BuildError::UnsupportedApsKeyError`] rather than being dropped. Everything else is kept
+    /// in [`custom`](Self::custom).
+    pub fn from_value(value: Value) -> Result<Self, BuildError> {
+        let Value::Object(object) = value else {
+            return NotAnObjectSnafu.fail();
+        };
+        ensure!(
+            matches!(object.get("aps"), Some(Value::Object(_))),
+            MissingApsSnafu
         );
+        if let Some(key) = object.get("aps").and_then(unknown_aps_key) {
+            return UnsupportedApsKeySnafu { key }.fail();
+        }
+        Self::from_map::<serde_json::Error>(object).context(DeserializeSnafu)
+    }
+
+    /// An MDM push, `{"mdm": push_magic}` with no `aps` dictionary, to send with
+    /// [`PushType::Mdm`] to the topic of the MDM push certificate.
+    ///
+    /// `aps` is left out as long as it stays empty and `mdm` stays the only custom key.
+    pub fn mdm(push_magic: impl Into<String>) -> Result<Self, BuildError> {
+        let push_magic = push_magic.into();
+        ensure!(!push_magic.is_empty(), EmptyPushMagicSnafu);
+        Ok(Self {
+            custom: Some(Map::from_iter([(
+                MDM_KEY.to_string(),
+                Value::String(push_magic),
+            )])),
+            ..Self::default()
+        })
+    }
+
+    /// Replaces the custom data with the fields of `custom`. Use
+    /// [`merge_custom`](Self::merge_custom) to keep keys that are already set.
+    pub fn with_custom<T: Serialize>(self, custom: T) -> Result<Self, BuildError> {
+        let custom = StructWrapper(custom)
+            .try_into()
+            .context(ConvertJsonObjectSnafu)?;
+        Ok(self.with_custom_map(custom))
+    }
+
+    /// Like [`with_custom`](Self::with_custom), but takes the custom data as an already built
+    /// JSON object, which is sent without another round trip through [`Serialize`].
+    pub fn with_custom_map(mut self, custom: Map<String, Value>) -> Self {
+        self.custom = Some(custom);
+        self
+    }
+
+    /// Adds the fields of `custom` to the custom data, replacing keys that are already set, such
+    /// as one from [`with_media_url`](Self::with_media_url).
+    pub fn merge_custom<T: Serialize>(mut self, custom: T) -> Result<Self, BuildError> {
+        let custom: Map<String, Value> = StructWrapper(custom)
+            .try_into()
+            .context(ConvertJsonObjectSnafu)?;
+        self.custom.get_or_insert_with(Map::new).extend(custom);
         Ok(self)
     }
+
+    /// Attaches a media URL for a Notification Service Extension under the `media-url` key.
+    ///
+    /// Also sets `mutable-content`, without which the extension is never run.
+    pub fn with_media_url(self, url: impl Into<String>) -> Self {
+        self.with_media_url_key(DEFAULT_MEDIA_URL_KEY, url)
+    }
+
+    /// Like [`with_media_url`](Self::with_media_url), for extensions reading another key.
+    pub fn with_media_url_key(mut self, key: &str, url: impl Into<String>) -> Self {
+        self.custom
+            .get_or_insert_with(Map::new)
+            .insert(key.to_string(), Value::String(url.into()));
+        self.aps.mutable_content = Some(true);
+        self
+    }
+
+    /// Asks a Notification Service Extension to add `by` to the badge, under the
+    /// `badge-increment` key.
+    ///
+    /// APNs can only set the badge, so this only works if the app's extension reads the key,
+    /// adds it to the badge it stored last, and sets the result on the delivered content. Also
+    /// sets `mutable-content`, without which the extension is never run.
+    pub fn with_badge_increment(mut self, by: i32) -> Self {
+        self.custom
+            .get_or_insert_with(Map::new)
+            .insert(BADGE_INCREMENT_KEY.to_string(), Value::from(by));
+        self.aps.mutable_content = Some(true);
+        self
+    }
+
+    /// The `aps` dictionary, for adjusting a single field of an already built payload.
+    pub fn aps_mut(&mut self) -> &mut Notification {
+        &mut self.aps
+    }
+
+    /// Serializes the payload without the `aps` dictionary while it is empty, sending only the
+    /// custom data. `aps` is sent by default, since PushKit and most other pushes expect it.
+    pub fn omit_empty_aps(&self) -> OmitEmptyAps<'_> {
+        OmitEmptyAps(self)
+    }
+
+    /// The serialized payload, for sending the same one to many devices with
+    /// [`APNClient::push_bytes`](crate::APNClient::push_bytes).
+    pub fn to_bytes(&self) -> Result<Bytes, BuildError> {
+        serde_json::to_vec(self)
+            .map(Bytes::from)
+            .context(SerializeSnafu)
+    }
+
+    /// Where the bytes of the serialized payload go, for finding what to trim when it is too
+    /// large.
+    pub fn size_breakdown(&self) -> Result<SizeBreakdown, BuildError> {
+        let total = self.to_bytes()?.len();
+        let aps_bytes = if self.includes_aps() {
+            // `"aps":` followed by the dictionary.
+            6 + serde_json::to_vec(&self.aps).context(SerializeSnafu)?.len()
+        } else {
+            0
+        };
+        Ok(SizeBreakdown {
+            aps_bytes,
+            custom_bytes: total - aps_bytes,
+            total,
+        })
+    }
+
+    /// Whether `aps` is serialized, which is always the case except for an [MDM](Self::mdm)
+    /// push.
+    fn includes_aps(&self) -> bool {
+        let is_mdm = self
+            .custom
+            .as_ref()
+            .is_some_and(|custom| custom.len() == 1 && custom.contains_key(MDM_KEY));
+        !is_mdm || self.aps != Notification::default()
+    }
+
+    fn serialize_entries<S: Serializer>(
+        &self,
+        serializer: S,
+        include_aps: bool,
+    ) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if include_aps {
+            map.serialize_entry("aps", &self.aps)?;
+        }
+        if let Some(custom) = &self.custom {
+            serialize_sorted_entries(custom, &mut map)?;
+        }
+        map.end()
+    }
+
+    /// The payload as JSON, for embedding into a larger structure.
+    pub fn to_value(&self) -> Result<Value, BuildError> {
+        serde_json::to_value(self).context(SerializeSnafu)
+    }
+
+    /// Checks the payload for content APNs would reject or silently ignore.
+    pub fn validate(&self) -> Result<(), BuildError> {
+        self.aps.validate()
+    }
+
+    /// Recursively removes `null` values from the custom data and the Live Activity content
+    /// state, which otherwise take up payload space. Nulls are kept unless this is called.
+    pub fn prune_nulls(mut self) -> Self {
+        if let Some(custom) = &mut self.custom {
+            prune_nulls(custom);
+        }
+        if let Some(content_state) = &mut self.aps.content_state {
+            prune_nulls(content_state);
+        }
+        self
+    }
 }
 
 pub struct Endpoint {
     endpoint: String,
     port: u16,
+    tls: bool,
 }
 
 impl From<Endpoint> for String {
     fn from(value: Endpoint) -> Self {
-            format!("https://{}:{}", value.endpoint, value.port)
+        let scheme = if value.tls { "https" } else { "http" };
+        format!("{}://{}:{}", scheme, value.endpoint, value.port)
     }
 }
 
@@ -200,15 +1124,27 @@ impl TryFrom<String> for Endpoint {
         Ok(Self {
             endpoint: value[0].to_string(),
             port: value[1].parse().map_err(|_| ())?,
+            tls: true,
         })
     }
 }
 
 impl Endpoint {
+    /// An unencrypted endpoint, spoken to with HTTP/2 prior knowledge. Only useful for testing.
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn plaintext(host: &str, port: u16) -> Self {
+        Self {
+            endpoint: host.to_string(),
+            port,
+            tls: false,
+        }
+    }
+
     pub fn development() -> Self {
         Self {
             endpoint: "api.sandbox.push.apple.com".to_string(),
             port: 443,
+            tls: true,
         }
     }
 
@@ -216,6 +1152,7 @@ impl Endpoint {
         Self {
             endpoint: "api.sandbox.push.apple.com".to_string(),
             port: 2197,
+            tls: true,
         }
     }
 
@@ -223,6 +1160,7 @@ impl Endpoint {
         Self {
             endpoint: "api.push.apple.com".to_string(),
             port: 443,
+            tls: true,
         }
     }
 
@@ -230,9 +1168,26 @@ impl Endpoint {
         Self {
             endpoint: "api.push.apple.com".to_string(),
             port: 2197,
+            tls: true,
         }
     }
-}
+
+    /// The production host on the same port.
+    pub fn to_production(self) -> Self {
+        Self {
+            endpoint: "api.push.apple.com".to_string(),
+            ..self
+        }
+    }
+
+    /// The sandbox host on the same port.
+    pub fn to_sandbox(self) -> Self {
+        Self {
+            endpoint: "api.sandbox.push.apple.com".to_string(),
+            ..self
+        }
+    }
+}
 
 impl Default for Endpoint {
     fn default() -> Self {
@@ -240,51 +1195,470 @@ impl Default for Endpoint {
     }
 }
 
-#[derive(Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PushType {
+    Alert,
+    Background,
+    Controls,
+    Location,
+    Voip,
+    Complication,
+    FileProvider,
+    Mdm,
+    LiveActivity,
+    PushToTalk,
+    Widgets,
+}
+
+impl PushType {
+    const ALL: [PushType; 11] = [
+        PushType::Alert,
+        PushType::Background,
+        PushType::Controls,
+        PushType::Location,
+        PushType::Voip,
+        PushType::Complication,
+        PushType::FileProvider,
+        PushType::Mdm,
+        PushType::LiveActivity,
+        PushType::PushToTalk,
+        PushType::Widgets,
+    ];
+
+    /// The value of the `apns-push-type` header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PushType::Alert => "alert",
+            PushType::Background => "background",
+            PushType::Controls => "controls",
+            PushType::Location => "location",
+            PushType::Voip => "voip",
+            PushType::Complication => "complication",
+            PushType::FileProvider => "fileprovider",
+            PushType::Mdm => "mdm",
+            PushType::LiveActivity => "liveactivity",
+            PushType::PushToTalk => "pushtotalk",
+            PushType::Widgets => "widgets",
+        }
+    }
+
+    /// The suffix APNs expects after the bundle id in `apns-topic`, if any.
+    ///
+    /// MDM pushes use the topic from the MDM certificate, so they have no suffix either.
+    pub fn topic_suffix(&self) -> Option<&'static str> {
+        match self {
+            PushType::Alert | PushType::Background | PushType::Mdm => None,
+            PushType::Controls => Some(".push-type.controls"),
+            PushType::Location => Some(".location-query"),
+            PushType::Voip => Some(".voip"),
+            PushType::Complication => Some(".complication"),
+            PushType::FileProvider => Some(".pushkit.fileprovider"),
+            PushType::LiveActivity => Some(".push-type.liveactivity"),
+            PushType::PushToTalk => Some(".voip-ptt"),
+            PushType::Widgets => Some(".push-type.widgets"),
+        }
+    }
+
+    /// The `apns-priority` conventionally sent with this push type, or `None` to leave it to
+    /// APNs. Background and File Provider updates must use `5`; anything time-sensitive `10`.
+    pub fn default_priority(&self) -> Option<u8> {
+        match self {
+            PushType::Background | PushType::FileProvider => Some(5),
+            PushType::Alert
+            | PushType::Location
+            | PushType::Voip
+            | PushType::Complication
+            | PushType::LiveActivity
+            | PushType::PushToTalk => Some(10),
+            PushType::Controls | PushType::Mdm | PushType::Widgets => None,
+        }
+    }
+
+    /// The `apns-priority` values APNs accepts without throttling for this push type.
+    pub fn allowed_priorities(&self) -> &'static [u8] {
+        match self {
+            PushType::Background | PushType::FileProvider => &[1, 5],
+            _ => &[1, 5, 10],
+        }
+    }
+}
+
+/// An `apns-topic`: the app's bundle id, suffixed according to the push type.
+///
+/// | Push type                             | Topic                              |
+/// |---------------------------------------|------------------------------------|
+/// | `alert`, `background`                 | `<bundle id>`                      |
+/// | `voip`                                | `<bundle id>.voip`                 |
+/// | `pushtotalk`                          | `<bundle id>.voip-ptt`             |
+/// | `complication`                        | `<bundle id>.complication`         |
+/// | `fileprovider`                        | `<bundle id>.pushkit.fileprovider` |
+/// | `location`                            | `<bundle id>.location-query`       |
+/// | `liveactivity`, `widgets`, `controls` | `<bundle id>.push-type.<type>`     |
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Topic(String);
+
+impl Topic {
+    pub fn new(topic: impl Into<String>) -> Self {
+        Self(topic.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The bundle id, without any push type suffix.
+    pub fn base(&self) -> &str {
+        match self.suffix() {
+            Some(suffix) => &self.0[..self.0.len() - suffix.len()],
+            None => &self.0,
+        }
+    }
+
+    /// The push type implied by the suffix, or `None` for a bare bundle id.
+    pub fn suffixed_push_type(&self) -> Option<PushType> {
+        PushType::ALL.into_iter().find(|push_type| {
+            push_type
+                .topic_suffix()
+                .is_some_and(|suffix| self.0.ends_with(suffix))
+        })
+    }
+
+    /// The topic for sending a push of `push_type` to the same app.
+    pub fn for_push_type(&self, push_type: PushType) -> Topic {
+        match push_type.topic_suffix() {
+            Some(suffix) => Topic(format!("{}{}", self.base(), suffix)),
+            None => Topic(self.base().to_string()),
+        }
+    }
+
+    /// The topic with everything but the first label of the bundle id and the push type suffix
+    /// redacted, e.g. `com.<redacted>.voip`, for logs that must not name the app.
+    pub fn redacted(&self) -> String {
+        let prefix = match self.base().split_once('.') {
+            Some((first, _)) => format!("{}.", first),
+            None => String::new(),
+        };
+        format!("{}<redacted>{}", prefix, self.suffix().unwrap_or_default())
+    }
+
+    fn suffix(&self) -> Option<&'static str> {
+        self.suffixed_push_type()
+            .and_then(|push_type| push_type.topic_suffix())
+    }
+}
+
+impl From<&str> for Topic {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for Topic {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+/// How long APNs keeps trying to deliver a push that could not be delivered right away, sent as
+/// `apns-expiration`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiration {
+    /// Leaves out `apns-expiration`, so APNs stores the push for as long as it sees fit.
+    StoreIndefinitely,
+    /// Sends `0`: APNs tries to deliver the push once and does not store it.
+    Immediately,
+    /// Keeps retrying until this many seconds since the UNIX epoch. `At(0)` is the same as
+    /// [`Immediately`](Self::Immediately).
+    At(u64),
+}
+
+/// What APNs does with a push it can not deliver right away, e.g. while the device is offline.
+///
+/// APNs stores at most one push per app on a device, so a newer stored push replaces the one
+/// before it, and retries only until the [`Expiration`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoragePolicy {
+    /// The push is dropped if the first attempt fails.
+    DoNotStore,
+    /// The push is stored, replacing any earlier one, and retried until it expires.
+    StoreLatest,
+}
+
+impl Expiration {
+    /// Expires `duration` after the current time of `clock`, e.g.
+    /// [`APNClient::clock`](crate::APNClient::clock). Saturates at [`u64::MAX`] seconds.
+    pub fn after(duration: Duration, clock: &dyn Clock) -> Result<Self, SystemTimeError> {
+        let now = clock.now().duration_since(UNIX_EPOCH)?;
+        Ok(Self::At(
+            now.checked_add(duration)
+                .map_or(u64::MAX, |at| at.as_secs()),
+        ))
+    }
+
+    /// The `apns-expiration` value, or `None` to leave the header out.
+    pub fn header_value(self) -> Option<u128> {
+        match self {
+            Self::StoreIndefinitely => None,
+            Self::Immediately => Some(0),
+            Self::At(seconds) => Some(seconds as u128),
+        }
+    }
+
+    pub fn storage_policy(self) -> StoragePolicy {
+        match self.header_value() {
+            Some(0) => StoragePolicy::DoNotStore,
+            _ => StoragePolicy::StoreLatest,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
 pub struct PushOption<'a> {
-    pub push_type: Option<&'a str>,
+    pub push_type: Option<PushType>,
+    /// An `apns-push-type` sent verbatim, for push types this crate does not know yet. Takes
+    /// precedence over [`push_type`](Self::push_type).
+    pub push_type_raw: Option<&'a str>,
     pub id: Option<&'a str>,
+    /// The `apns-id` as a UUID, formatted in canonical form. Used when [`id`](Self::id) is not
+    /// set.
+    #[cfg(feature = "uuid")]
+    pub id_uuid: Option<Uuid>,
     pub expiration: Option<u128>,
     pub priority: Option<u8>,
     pub topic: &'a str,
     pub collapse_id: Option<&'a str>,
+    /// Overrides the client's [timeout](crate::APNClientConfig::with_timeout) for this push.
+    pub timeout: Option<Duration>,
+    /// Overrides the client's [retry policy](crate::APNClientConfig::with_retry_policy) for this
+    /// push.
+    pub retry_policy: Option<&'a RetryPolicy>,
+}
+
+impl<'a> PushOption<'a> {
+    /// Fills every field this option leaves unset from `defaults`.
+    ///
+    /// A field is unset when it is `None`, or for the topic, empty. The push type counts as set
+    /// if either [`push_type`](Self::push_type) or [`push_type_raw`](Self::push_type_raw) is,
+    /// and then both are kept from this option. The `apns-id` is never taken from `defaults`,
+    /// since it must be unique per push.
+    pub fn with_defaults(self, defaults: PushOption<'a>) -> Self {
+        let (push_type, push_type_raw) = match (self.push_type, self.push_type_raw) {
+            (None, None) => (defaults.push_type, defaults.push_type_raw),
+            set => set,
+        };
+        Self {
+            push_type,
+            push_type_raw,
+            id: self.id,
+            #[cfg(feature = "uuid")]
+            id_uuid: self.id_uuid,
+            expiration: self.expiration.or(defaults.expiration),
+            priority: self.priority.or(defaults.priority),
+            topic: if self.topic.is_empty() {
+                defaults.topic
+            } else {
+                self.topic
+            },
+            collapse_id: self.collapse_id.or(defaults.collapse_id),
+            timeout: self.timeout.or(defaults.timeout),
+            retry_policy: self.retry_policy.or(defaults.retry_policy),
+        }
+    }
+
+    /// Checks the priority against the [allowed priorities](PushType::allowed_priorities) of
+    /// the push type, if both are set. A background push sent with priority `10` is a common
+    /// cause of throttling.
+    pub fn validate(&self) -> Result<(), PushOptionError> {
+        if let (Some(push_type), Some(priority)) = (self.push_type, self.priority) {
+            ensure!(
+                push_type.allowed_priorities().contains(&priority),
+                PriorityNotAllowedSnafu {
+                    priority,
+                    push_type
+                }
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether an `apns-id` is set, as a string or a UUID.
+    #[cfg(feature = "uuid")]
+    pub(crate) fn has_id(&self) -> bool {
+        self.id.is_some() || self.id_uuid.is_some()
+    }
+
+    /// Sets [`expiration`](Self::expiration) from `expiration`.
+    pub fn with_expiration(mut self, expiration: Expiration) -> Self {
+        self.expiration = expiration.header_value();
+        self
+    }
+
+    /// Options for `push_type` with its [default priority](PushType::default_priority).
+    pub fn default_for(push_type: PushType, topic: &'a str) -> Self {
+        Self {
+            push_type: Some(push_type),
+            priority: push_type.default_priority(),
+            topic,
+            ..Self::default()
+        }
+    }
+
+    /// Options for a user-visible alert: push type `alert`, priority `10`.
+    pub fn alert(topic: &'a str) -> Self {
+        Self::default_for(PushType::Alert, topic)
+    }
+
+    /// Options for a silent background update: push type `background`, priority `5`.
+    pub fn background(topic: &'a str) -> Self {
+        Self::default_for(PushType::Background, topic)
+    }
+
+    /// Options for a Live Activity update: push type `liveactivity`, priority `10`.
+    ///
+    /// `topic` may be the bare bundle id, in which case `.push-type.liveactivity` is appended.
+    pub fn live_activity(topic: &'a str) -> Self {
+        Self::default_for(PushType::LiveActivity, topic)
+    }
+
+    /// Options for a push APNs drops unless it can deliver it within `duration` from now. See
+    /// [`Expiration::after`].
+    pub fn expires_in(topic: &'a str, duration: Duration) -> Result<Self, SystemTimeError> {
+        Self::expires_in_with_clock(topic, duration, &SystemClock)
+    }
+
+    /// Like [`expires_in`](Self::expires_in), counting from the current time of `clock`, e.g.
+    /// [`APNClient::clock`](crate::APNClient::clock).
+    pub fn expires_in_with_clock(
+        topic: &'a str,
+        duration: Duration,
+        clock: &dyn Clock,
+    ) -> Result<Self, SystemTimeError> {
+        Ok(Self {
+            topic,
+            ..Self::default()
+        }
+        .with_expiration(Expiration::after(duration, clock)?))
+    }
+
+    /// Options for a PushKit VoIP push: push type `voip`, priority `10`.
+    pub fn voip(topic: &'a str) -> Self {
+        Self::default_for(PushType::Voip, topic)
+    }
 }
 
+pub(crate) const APNS_PUSH_TYPE: HeaderName = HeaderName::from_static("apns-push-type");
+pub(crate) const APNS_ID: HeaderName = HeaderName::from_static("apns-id");
+pub(crate) const APNS_EXPIRATION: HeaderName = HeaderName::from_static("apns-expiration");
+pub(crate) const APNS_PRIORITY: HeaderName = HeaderName::from_static("apns-priority");
+pub(crate) const APNS_COLLAPSE_ID: HeaderName = HeaderName::from_static("apns-collapse-id");
+pub(crate) const APNS_TOPIC: HeaderName = HeaderName::from_static("apns-topic");
+
 impl TryFrom<PushOption<'_>> for HeaderMap {
-    type Error = ();
+    type Error = PushOptionError;
 
     fn try_from(value: PushOption) -> Result<Self, Self::Error> {
-        let mut headers = Self::new();
-        if let Some(push_type) = value.push_type {
-            headers.insert("apns-push-type", push_type.parse().map_err(|_| ())?);
+        let mut headers = Self::with_capacity(6);
+        let push_type = value
+            .push_type_raw
+            .or(value.push_type.map(|push_type| push_type.as_str()));
+        match (value.push_type_raw, value.push_type) {
+            (Some(raw), _) => {
+                headers.insert(APNS_PUSH_TYPE, parse_header("apns-push-type", raw)?);
+            }
+            (None, Some(push_type)) => {
+                headers.insert(APNS_PUSH_TYPE, HeaderValue::from_static(push_type.as_str()));
+            }
+            (None, None) => {}
         }
         if let Some(id) = value.id {
-            headers.insert("apns-id", id.parse().map_err(|_| ())?);
+            headers.insert(APNS_ID, parse_header("apns-id", id)?);
+        }
+        #[cfg(feature = "uuid")]
+        if let (None, Some(uuid)) = (value.id, value.id_uuid) {
+            let mut buffer = Uuid::encode_buffer();
+            let id = uuid.hyphenated().encode_lower(&mut buffer);
+            headers.insert(APNS_ID, parse_header("apns-id", id)?);
         }
+        // Numeric headers are formatted on the stack rather than through `to_string`.
+        let mut buffer = itoa::Buffer::new();
         if let Some(expiration) = value.expiration {
             headers.insert(
-                "apns-expiration",
-                expiration.to_string().parse().map_err(|_| ())?,
+                APNS_EXPIRATION,
+                parse_header("apns-expiration", buffer.format(expiration))?,
             );
         }
         if let Some(priority) = value.priority {
-            headers.insert(
-                "apns-priority",
-                priority.to_string().parse().map_err(|_| ())?,
-            );
+            let priority = match priority {
+                10 => HeaderValue::from_static("10"),
+                5 => HeaderValue::from_static("5"),
+                1 => HeaderValue::from_static("1"),
+                _ => parse_header("apns-priority", buffer.format(priority))?,
+            };
+            headers.insert(APNS_PRIORITY, priority);
         }
         if let Some(collapse_id) = value.collapse_id {
-            headers.insert("apns-collapse-id", collapse_id.parse().map_err(|_| ())?);
+            headers.insert(
+                APNS_COLLAPSE_ID,
+                parse_header("apns-collapse-id", collapse_id)?,
+            );
         }
-        headers.insert("apns-topic", value.topic.parse().map_err(|_| ())?);
+        let topic = Topic::new(value.topic);
+        let topic = match push_type {
+            Some(push_type) if push_type == PushType::LiveActivity.as_str() => {
+                live_activity_topic(topic)?
+            }
+            _ => topic,
+        };
+        headers.insert(APNS_TOPIC, parse_header("apns-topic", topic.as_str())?);
         Ok(headers)
     }
 }
 
+fn parse_header(name: &'static str, value: &str) -> Result<HeaderValue, PushOptionError> {
+    value.parse().ok().context(InvalidHeaderSnafu { name })
+}
+
+/// Live Activity topics need the `.push-type.liveactivity` suffix, which is added to a bare
+/// bundle id. A topic suffixed for another push type is rejected.
+fn live_activity_topic(topic: Topic) -> Result<Topic, PushOptionError> {
+    match topic.suffixed_push_type() {
+        Some(PushType::LiveActivity) => Ok(topic),
+        None if !topic.as_str().is_empty() => Ok(topic.for_push_type(PushType::LiveActivity)),
+        _ => TopicMismatchSnafu {
+            topic: topic.as_str(),
+            push_type: PushType::LiveActivity,
+        }
+        .fail(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::{
+        Alert, Body, BuildError, Clock, DismissalPolicy, Endpoint, Expiration, InterruptionLevel,
+        Notification, ParseError, Payload, PushOption, PushOptionError, PushType, Sound,
+        StoragePolicy, StrictPayload, Subtitle, Title, Topic,
+    };
+    use reqwest::header::HeaderMap;
     use serde::Serialize;
-    use crate::{Alert, InterruptionLevel, Notification, Payload, Sound, Subtitle, Title};
+    use serde_json::{Map, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Level, Metadata, Subscriber};
+
+    use super::APS_KEYS;
+
+    struct FixedClock(SystemTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> SystemTime {
+            self.0
+        }
+    }
 
     #[test]
     fn test_empty() {
@@ -293,6 +1667,32 @@ mod tests {
         assert_eq!("{}", json);
     }
 
+    #[test]
+    fn test_empty_payload() {
+        // APNs requires the aps dictionary, even when it is empty.
+        let payload = Payload::default();
+        assert_eq!("{\"aps\":{}}", serde_json::to_string(&payload).unwrap());
+        assert_eq!(&b"{\"aps\":{}}"[..], payload.to_bytes().unwrap());
+        assert_eq!(
+            "{\"aps\":{}}",
+            serde_json::to_string(&Payload::from(Notification::default())).unwrap()
+        );
+
+        let payload = Payload {
+            custom: Some(Map::from_iter([("id".to_string(), Value::from(7))])),
+            ..Payload::default()
+        };
+        assert_eq!(
+            "{\"aps\":{},\"id\":7}",
+            serde_json::to_string(&payload).unwrap()
+        );
+        let payload = serde_json::from_str::<Payload>("{\"aps\":{},\"id\":7}").unwrap();
+        assert_eq!(
+            "{\"aps\":{},\"id\":7}",
+            serde_json::to_string(&payload).unwrap()
+        );
+    }
+
     #[test]
     fn test_filled() {
         #[derive(Serialize)]
@@ -313,6 +1713,8 @@ mod tests {
                 }),
                 body: None,
                 launch_image: None,
+                summary_arg: None,
+                summary_arg_count: None,
             }),
             sound: Some(Sound::Critical {
                 critical: Some(true),
@@ -350,4 +1752,1053 @@ mod tests {
         let json = serde_json::to_string(&notification).unwrap();
         assert_eq!("{\"aps\":{},\"payload\":\"payload\"}", json)
     }
+
+    #[test]
+    fn test_push_option_presets() {
+        let headers = HeaderMap::try_from(PushOption::alert("com.example.app")).unwrap();
+        assert_eq!("alert", headers["apns-push-type"]);
+        assert_eq!("10", headers["apns-priority"]);
+        assert_eq!("com.example.app", headers["apns-topic"]);
+
+        let headers = HeaderMap::try_from(PushOption::background("com.example.app")).unwrap();
+        assert_eq!("background", headers["apns-push-type"]);
+        assert_eq!("5", headers["apns-priority"]);
+
+        let headers = HeaderMap::try_from(PushOption::voip("com.example.app.voip")).unwrap();
+        assert_eq!("voip", headers["apns-push-type"]);
+        assert_eq!("10", headers["apns-priority"]);
+    }
+
+    #[test]
+    fn test_push_option_with_defaults() {
+        let defaults = PushOption {
+            push_type: Some(PushType::Background),
+            id: Some("EEEEEEEE-0000-0000-0000-000000000001"),
+            priority: Some(5),
+            topic: "com.example.app",
+            ..PushOption::default()
+        };
+        let option = PushOption {
+            push_type_raw: Some("newtype"),
+            ..PushOption::default()
+        }
+        .with_defaults(defaults);
+        assert_eq!(None, option.push_type);
+        assert_eq!(Some("newtype"), option.push_type_raw);
+        assert_eq!(None, option.id);
+        assert_eq!(Some(5), option.priority);
+        assert_eq!("com.example.app", option.topic);
+    }
+
+    #[test]
+    fn test_numeric_headers() {
+        for (expiration, priority) in [(0, 0), (1_700_000_000, 5), (u128::MAX, u8::MAX)] {
+            let option = PushOption {
+                expiration: Some(expiration),
+                priority: Some(priority),
+                topic: "com.example.app",
+                ..PushOption::default()
+            };
+            let headers = HeaderMap::try_from(option).unwrap();
+            assert_eq!(expiration.to_string(), headers["apns-expiration"]);
+            assert_eq!(priority.to_string(), headers["apns-priority"]);
+        }
+    }
+
+    #[test]
+    fn test_live_activity_topic() {
+        let headers = HeaderMap::try_from(PushOption::live_activity("com.example.app")).unwrap();
+        assert_eq!("liveactivity", headers["apns-push-type"]);
+        assert_eq!(
+            "com.example.app.push-type.liveactivity",
+            headers["apns-topic"]
+        );
+
+        let option = PushOption::live_activity("com.example.app.push-type.liveactivity");
+        let headers = HeaderMap::try_from(option).unwrap();
+        assert_eq!(
+            "com.example.app.push-type.liveactivity",
+            headers["apns-topic"]
+        );
+
+        let error = HeaderMap::try_from(PushOption::live_activity("com.example.app.voip"));
+        assert!(matches!(
+            error,
+            Err(PushOptionError::TopicMismatchError { topic, .. }) if topic == "com.example.app.voip"
+        ));
+    }
+
+    #[test]
+    fn test_custom_map() {
+        let custom = serde_json::json!({ "price": 1.50, "ids": [3, 1, 2] });
+        let Value::Object(custom) = custom else {
+            panic!("custom data is not an object");
+        };
+        let payload = Payload::default().with_custom_map(custom);
+        assert_eq!(
+            r#"{"aps":{},"ids":[3,1,2],"price":1.5}"#,
+            serde_json::to_string(&payload).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_size_breakdown() {
+        let payload = Payload::from(Notification {
+            badge: Some(3),
+            ..Notification::default()
+        })
+        .with_custom(HashMap::from([("blob", "x".repeat(100))]))
+        .unwrap();
+        let breakdown = payload.size_breakdown().unwrap();
+        assert_eq!(payload.to_bytes().unwrap().len(), breakdown.total);
+        assert_eq!(
+            breakdown.total,
+            breakdown.aps_bytes + breakdown.custom_bytes
+        );
+        assert_eq!(r#""aps":{"badge":3}"#.len(), breakdown.aps_bytes);
+
+        let payload = Payload::mdm("push-magic").unwrap();
+        assert_eq!(0, payload.size_breakdown().unwrap().aps_bytes);
+    }
+
+    #[test]
+    fn test_badge_increment() {
+        let payload = Payload::default().with_badge_increment(-2);
+        assert_eq!(
+            serde_json::json!({
+                "aps": { "mutable-content": 1 },
+                "badge-increment": -2,
+            }),
+            serde_json::to_value(&payload).unwrap()
+        );
+
+        let payload = Payload::default()
+            .with_badge_increment(1)
+            .with_media_url("https://example.com/cat.jpg")
+            .merge_custom(HashMap::from([("id", 7)]))
+            .unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "aps": { "mutable-content": 1 },
+                "badge-increment": 1,
+                "id": 7,
+                "media-url": "https://example.com/cat.jpg",
+            }),
+            serde_json::to_value(&payload).unwrap()
+        );
+
+        let payload = Payload::default()
+            .with_badge_increment(1)
+            .with_custom(HashMap::from([("id", 7)]))
+            .unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "aps": { "mutable-content": 1 },
+                "id": 7,
+            }),
+            serde_json::to_value(&payload).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_media_url() {
+        let payload = Payload::default().with_media_url("https://example.com/cat.jpg");
+        assert_eq!(
+            serde_json::json!({
+                "aps": { "mutable-content": 1 },
+                "media-url": "https://example.com/cat.jpg",
+            }),
+            serde_json::to_value(&payload).unwrap()
+        );
+
+        let payload = Payload::default()
+            .with_custom(HashMap::from([("id", 7)]))
+            .unwrap()
+            .with_media_url_key("image", "https://example.com/dog.png");
+        assert_eq!(
+            serde_json::json!({
+                "aps": { "mutable-content": 1 },
+                "id": 7,
+                "image": "https://example.com/dog.png",
+            }),
+            serde_json::to_value(&payload).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_mdm() {
+        let payload = Payload::mdm("2A4D1E9B-0C37-4B5E-8F6A-3D2C1B0A9E8F").unwrap();
+        assert_eq!(
+            r#"{"mdm":"2A4D1E9B-0C37-4B5E-8F6A-3D2C1B0A9E8F"}"#,
+            serde_json::to_string(&payload).unwrap()
+        );
+        assert!(matches!(
+            Payload::mdm(""),
+            Err(BuildError::EmptyPushMagicError)
+        ));
+    }
+
+    #[test]
+    fn test_from_value() {
+        let value = serde_json::json!({
+            "aps": { "alert": "Hello", "badge": 3 },
+            "id": 7,
+        });
+        let payload = Payload::from_value(value.clone()).unwrap();
+        assert_eq!(value, serde_json::to_value(&payload).unwrap());
+
+        assert!(matches!(
+            Payload::from_value(serde_json::json!([1, 2])),
+            Err(BuildError::NotAnObjectError)
+        ));
+        assert!(matches!(
+            Payload::from_value(serde_json::json!({ "id": 7 })),
+            Err(BuildError::MissingApsError)
+        ));
+        assert!(matches!(
+            Payload::from_value(serde_json::json!({ "aps": "alert" })),
+            Err(BuildError::MissingApsError)
+        ));
+        assert!(matches!(
+            Payload::from_value(serde_json::json!({ "aps": { "badge": "three" } })),
+            Err(BuildError::DeserializeError { .. })
+        ));
+        assert!(matches!(
+            Payload::from_value(serde_json::json!({ "aps": { "alert": "Hi", "mutable-contents": 1 } })),
+            Err(BuildError::UnsupportedApsKeyError { key }) if key == "mutable-contents"
+        ));
+        assert!(matches!(
+            Payload::from_value(serde_json::json!({ "aps": { "alert": { "tittle": "Hi" } } })),
+            Err(BuildError::UnsupportedApsKeyError { key }) if key == "alert.tittle"
+        ));
+    }
+
+    #[test]
+    fn test_from_value_aps_mut() {
+        let mut payload = Payload::from_value(serde_json::json!({
+            "aps": { "alert": "Hello" },
+            "id": 7,
+        }))
+        .unwrap();
+        payload.aps_mut().badge = Some(3);
+        assert_eq!(
+            r#"{"aps":{"alert":"Hello","badge":3},"id":7}"#,
+            serde_json::to_string(&payload).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_aps_mut() {
+        let mut payload = Payload::from(Notification {
+            alert: Some(Alert::Body("New message".to_string())),
+            ..Notification::default()
+        });
+        payload.aps_mut().badge = Some(4);
+        assert!(payload.validate().is_ok());
+        assert_eq!(
+            serde_json::json!({ "aps": { "alert": "New message", "badge": 4 } }),
+            serde_json::to_value(&payload).unwrap()
+        );
+
+        payload.aps_mut().alert = Some(Alert::Body(String::new()));
+        assert!(payload.validate().is_err());
+    }
+
+    #[test]
+    fn test_to_value() {
+        let payload = Payload::from(Notification {
+            alert: Some(Alert::Body("Hello".to_string())),
+            badge: Some(1),
+            ..Notification::default()
+        })
+        .with_custom(HashMap::from([("id", 7)]))
+        .unwrap();
+        let json = serde_json::to_string(&payload).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            payload.to_value().unwrap()
+        );
+        assert_eq!(
+            serde_json::json!({ "alert": "Hello", "badge": 1 }),
+            payload.aps.to_value().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_silent() {
+        let notification = Notification::silent();
+        assert!(notification.is_silent());
+        assert_eq!(
+            serde_json::json!({ "content-available": 1 }),
+            notification.to_value().unwrap()
+        );
+
+        let visible = Notification {
+            badge: Some(1),
+            ..Notification::silent()
+        };
+        assert!(!visible.is_silent());
+
+        let (result, warnings) = count_warnings(|| visible.validate());
+        assert!(result.is_ok());
+        assert_eq!(0, warnings);
+
+        let alerting = Notification {
+            alert: Some(Alert::Body("Body".to_string())),
+            ..Notification::silent()
+        };
+        assert!(matches!(
+            alerting.validate(),
+            Err(BuildError::ConflictingFieldsError {
+                field: "content-available",
+                other: "alert",
+            })
+        ));
+        let sounding = Notification {
+            sound: Some(Sound::Regular("default".to_string())),
+            ..Notification::silent()
+        };
+        assert!(matches!(
+            sounding.validate(),
+            Err(BuildError::ConflictingFieldsError {
+                field: "content-available",
+                other: "sound",
+            })
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_payload() {
+        let json = r#"{"aps":{"alert":"Hi","badge":2,"content-available":1},"id":7}"#;
+        let payload = serde_json::from_str::<Payload>(json).unwrap();
+        assert_eq!(Some(2), payload.aps.badge);
+        assert_eq!(Some(true), payload.aps.content_available);
+        assert_eq!(json, serde_json::to_string(&payload).unwrap());
+
+        let payload = serde_json::from_str::<Payload>(r#"{"sync":true}"#).unwrap();
+        assert_eq!(Notification::default(), payload.aps);
+        assert_eq!(
+            r#"{"sync":true}"#,
+            serde_json::to_string(&payload.omit_empty_aps()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_strict_payload() {
+        let json = r#"{"aps":{"mutable-contents":1},"id":7}"#;
+        assert!(serde_json::from_str::<Payload>(json).is_ok());
+        let error = serde_json::from_str::<StrictPayload>(json).unwrap_err();
+        assert!(error.to_string().contains("mutable-contents"));
+
+        let json = r#"{"aps":{"mutable-content":1},"anything":{"goes":true}}"#;
+        let StrictPayload(payload) = serde_json::from_str(json).unwrap();
+        assert_eq!(Some(true), payload.aps.mutable_content);
+
+        let StrictPayload(payload) = StrictPayload::parse(json).unwrap();
+        assert_eq!(Some(true), payload.aps.mutable_content);
+        assert!(payload.custom.unwrap().contains_key("anything"));
+    }
+
+    #[test]
+    fn test_parse_strict() {
+        let json = r#"{"aps":{"alert":"Hello","mutable-contents":1},"id":7}"#;
+        assert!(matches!(
+            Notification::parse_strict(json),
+            Err(ParseError::UnknownApsKeyError { key }) if key == "mutable-contents"
+        ));
+        let json = r#"{"aps":{"alert":{"tittle":"Hello"}}}"#;
+        assert!(matches!(
+            Notification::parse_strict(json),
+            Err(ParseError::UnknownApsKeyError { key }) if key == "alert.tittle"
+        ));
+        assert!(serde_json::from_str::<StrictPayload>(json).is_err());
+        assert!(matches!(
+            Notification::parse_strict(r#"{"aps":{"badge":"one"}}"#),
+            Err(ParseError::InvalidApsError { .. })
+        ));
+        assert!(matches!(
+            Notification::parse_strict("[]"),
+            Err(ParseError::MalformedJsonError { .. })
+        ));
+        assert!(matches!(
+            Notification::parse_strict(r#"{"id":7}"#),
+            Err(ParseError::MissingApsDictionaryError)
+        ));
+
+        let json = r#"{"aps":{"alert":"Hello","mutable-content":1},"id":7}"#;
+        let notification = Notification::parse_strict(json).unwrap();
+        assert_eq!(Some(Alert::Body("Hello".to_string())), notification.alert);
+        assert_eq!(Some(true), notification.mutable_content);
+    }
+
+    #[test]
+    fn test_parse_strict_input_push_token() {
+        let json = r#"{"aps":{"event":"start","input-push-token":1}}"#;
+        let notification = Notification::parse_strict(json).unwrap();
+        assert_eq!(Some(true), notification.input_push_token);
+        assert_eq!(
+            serde_json::json!({ "event": "start", "input-push-token": 1 }),
+            notification.to_value().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_strict_input_push_channel() {
+        let json = r#"{"aps":{"event":"start","input-push-channel":"dHN0LXNyY2gtY2hubA=="}}"#;
+        let notification = Notification::parse_strict(json).unwrap();
+        assert_eq!(
+            Some("dHN0LXNyY2gtY2hubA==".to_string()),
+            notification.input_push_channel
+        );
+        assert_eq!(
+            serde_json::json!({ "event": "start", "input-push-channel": "dHN0LXNyY2gtY2hubA==" }),
+            notification.to_value().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_aps_keys() {
+        let notification = Notification {
+            alert: Some(Alert::Body("Body".to_string())),
+            badge: Some(1),
+            sound: Some(Sound::Regular("default".to_string())),
+            thread_id: Some("thread".to_string()),
+            category: Some("category".to_string()),
+            content_available: Some(true),
+            mutable_content: Some(true),
+            target_content_id: Some("target".to_string()),
+            interruption_level: Some(InterruptionLevel::Active),
+            relevance_score: Some(0.5),
+            filter_criteria: Some("work".to_string()),
+            stale_date: Some(1),
+            content_state: Some(Map::new()),
+            timestamp: Some(1),
+            event: Some("update".to_string()),
+            dismissal_date: Some(1),
+            attributes_type: Some("Attributes".to_string()),
+            attributes: Some(Map::new()),
+            input_push_token: Some(true),
+            input_push_channel: Some("channel".to_string()),
+        };
+        let Value::Object(aps) = notification.to_value().unwrap() else {
+            panic!("aps is not an object");
+        };
+        let keys = aps.keys().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(APS_KEYS.len(), keys.len());
+        assert!(keys.iter().all(|key| APS_KEYS.contains(key)));
+    }
+
+    #[test]
+    fn test_omit_empty_aps() {
+        let payload = Payload::default()
+            .with_custom(HashMap::from([("sync", true)]))
+            .unwrap();
+        assert_eq!(
+            "{\"aps\":{},\"sync\":true}",
+            serde_json::to_string(&payload).unwrap()
+        );
+
+        assert_eq!(
+            "{\"sync\":true}",
+            serde_json::to_string(&payload.omit_empty_aps()).unwrap()
+        );
+        assert_eq!(
+            &b"{\"sync\":true}"[..],
+            payload.omit_empty_aps().to_bytes().unwrap()
+        );
+
+        let payload = Payload {
+            aps: Notification {
+                badge: Some(1),
+                ..Notification::default()
+            },
+            ..payload
+        };
+        assert_eq!(
+            "{\"aps\":{\"badge\":1},\"sync\":true}",
+            serde_json::to_string(&payload.omit_empty_aps()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_alert_accessors() {
+        let alert: Alert = serde_json::from_value(serde_json::json!({
+            "title": "Hello",
+            "subtitle-loc-key": "SUBTITLE",
+            "subtitle-loc-args": ["Jane"],
+            "loc-key": "BODY",
+        }))
+        .unwrap();
+        assert_eq!(Some("Hello"), alert.title());
+        assert_eq!(Some("SUBTITLE"), alert.subtitle());
+        assert_eq!(Some("BODY"), alert.body());
+        match &alert {
+            Alert::Full {
+                title: Some(title),
+                subtitle: Some(subtitle),
+                body: Some(body),
+                ..
+            } => {
+                assert!(!title.is_localized());
+                assert!(title.args().is_empty());
+                assert!(subtitle.is_localized());
+                assert_eq!(["Jane".to_string()], subtitle.args());
+                assert!(body.is_localized());
+            }
+            _ => panic!("expected a full alert"),
+        }
+
+        let alert: Alert = serde_json::from_value(serde_json::json!({ "title": "Hi" })).unwrap();
+        assert_eq!(Some("Hi"), alert.title());
+        assert_eq!(None, alert.subtitle());
+        assert_eq!(None, alert.body());
+
+        let alert: Alert = serde_json::from_value(serde_json::json!("Just a body")).unwrap();
+        assert_eq!(None, alert.title());
+        assert_eq!(Some("Just a body"), alert.body());
+    }
+
+    #[test]
+    fn test_summary_arg() {
+        let alert = Alert::Body("New message".to_string())
+            .with_summary_arg("Jane")
+            .with_summary_arg_count(3);
+        assert_eq!(
+            serde_json::json!({
+                "body": "New message",
+                "summary-arg": "Jane",
+                "summary-arg-count": 3,
+            }),
+            serde_json::to_value(&alert).unwrap()
+        );
+
+        let parsed: Alert = serde_json::from_value(serde_json::to_value(&alert).unwrap()).unwrap();
+        assert_eq!(alert, parsed);
+    }
+
+    #[test]
+    fn test_raw_push_type() {
+        let option = PushOption {
+            push_type_raw: Some("futuretype"),
+            ..PushOption::alert("com.example.app")
+        };
+        let headers = HeaderMap::try_from(option).unwrap();
+        assert_eq!("futuretype", headers["apns-push-type"]);
+
+        let option = PushOption {
+            push_type_raw: Some("bad\ntype"),
+            ..PushOption::alert("com.example.app")
+        };
+        assert!(matches!(
+            HeaderMap::try_from(option),
+            Err(PushOptionError::InvalidHeaderError {
+                name: "apns-push-type"
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decimal_notation() {
+        let aps = Notification {
+            relevance_score: Some(0.0000001),
+            sound: Some(Sound::Critical {
+                critical: Some(true),
+                name: None,
+                volume: Some(0.5),
+            }),
+            ..Notification::default()
+        };
+        let json = serde_json::to_string(&aps).unwrap();
+        assert_eq!(
+            "{\"sound\":{\"critical\":1,\"volume\":0.5},\"relevance-score\":0.0000001}",
+            json
+        );
+
+        let aps = Notification {
+            relevance_score: Some(1.5),
+            ..Notification::default()
+        };
+        assert!(serde_json::to_string(&aps).is_err());
+    }
+
+    #[test]
+    fn test_priority_for_push_type() {
+        let option = PushOption {
+            priority: Some(10),
+            ..PushOption::background("com.example.app")
+        };
+        assert!(matches!(
+            option.validate(),
+            Err(PushOptionError::PriorityNotAllowedError {
+                priority: 10,
+                push_type: PushType::Background
+            })
+        ));
+        assert!(PushOption::background("com.example.app").validate().is_ok());
+        assert!(PushOption::alert("com.example.app").validate().is_ok());
+    }
+
+    #[test]
+    fn test_endpoint_environment() {
+        assert_eq!(
+            "https://api.push.apple.com:2197",
+            String::from(Endpoint::development_alter().to_production())
+        );
+        assert_eq!(
+            "https://api.sandbox.push.apple.com:443",
+            String::from(Endpoint::production().to_sandbox())
+        );
+    }
+
+    #[test]
+    fn test_expiration_headers() {
+        for (expiration, header, policy) in [
+            (
+                Expiration::StoreIndefinitely,
+                None,
+                StoragePolicy::StoreLatest,
+            ),
+            (
+                Expiration::Immediately,
+                Some("0"),
+                StoragePolicy::DoNotStore,
+            ),
+            (Expiration::At(0), Some("0"), StoragePolicy::DoNotStore),
+            (
+                Expiration::At(1_700_000_000),
+                Some("1700000000"),
+                StoragePolicy::StoreLatest,
+            ),
+        ] {
+            let option = PushOption::alert("com.example.app").with_expiration(expiration);
+            let headers = HeaderMap::try_from(option).unwrap();
+            assert_eq!(
+                header,
+                headers
+                    .get("apns-expiration")
+                    .map(|value| value.to_str().unwrap())
+            );
+            assert_eq!(policy, expiration.storage_policy());
+        }
+    }
+
+    #[test]
+    fn test_expires_in() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u128;
+        let option = PushOption::expires_in("com.example.app", Duration::from_secs(600)).unwrap();
+        let expiration = option.expiration.unwrap();
+        assert!((now + 600..=now + 601).contains(&expiration));
+        assert_eq!("com.example.app", option.topic);
+
+        let clock = FixedClock(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let option =
+            PushOption::expires_in_with_clock("com.example.app", Duration::from_secs(600), &clock)
+                .unwrap();
+        assert_eq!(Some(1_700_000_600), option.expiration);
+        assert_eq!("com.example.app", option.topic);
+
+        assert_eq!(
+            Expiration::At(u64::MAX),
+            Expiration::after(Duration::MAX, &clock).unwrap()
+        );
+        assert!(
+            Expiration::after(
+                Duration::ZERO,
+                &FixedClock(UNIX_EPOCH - Duration::from_secs(1))
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_default_for() {
+        let option = PushOption::default_for(PushType::Background, "com.example.app");
+        assert_eq!(Some(PushType::Background), option.push_type);
+        assert_eq!(Some(5), option.priority);
+        assert_eq!("com.example.app", option.topic);
+        assert_eq!(
+            Some(10),
+            PushOption::default_for(PushType::Alert, "com.example.app").priority
+        );
+        assert_eq!(
+            None,
+            PushOption::default_for(PushType::Widgets, "com.example.app").priority
+        );
+
+        let topic = "com.example.app";
+        for (preset, push_type) in [
+            (PushOption::alert(topic), PushType::Alert),
+            (PushOption::background(topic), PushType::Background),
+            (PushOption::live_activity(topic), PushType::LiveActivity),
+            (PushOption::voip(topic), PushType::Voip),
+        ] {
+            assert_eq!(
+                HeaderMap::try_from(PushOption::default_for(push_type, topic)).unwrap(),
+                HeaderMap::try_from(preset).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_topic_suffix() {
+        let topic = Topic::new("com.example.app.voip");
+        assert_eq!("com.example.app", topic.base());
+        assert_eq!("com.<redacted>.voip", topic.redacted());
+        assert_eq!("com.<redacted>", Topic::new("com.example.app").redacted());
+        assert_eq!("<redacted>", Topic::new("app").redacted());
+        assert_eq!(Some(PushType::Voip), topic.suffixed_push_type());
+        assert_eq!(
+            "com.example.app",
+            topic.for_push_type(PushType::Alert).as_str()
+        );
+        assert_eq!(
+            "com.example.app.push-type.liveactivity",
+            topic.for_push_type(PushType::LiveActivity).as_str()
+        );
+
+        let topic = Topic::new("com.example.app.voip-ptt");
+        assert_eq!("com.example.app", topic.base());
+        assert_eq!(Some(PushType::PushToTalk), topic.suffixed_push_type());
+
+        let topic = Topic::new("com.example.app");
+        assert_eq!("com.example.app", topic.base());
+        assert_eq!(None, topic.suffixed_push_type());
+        assert_eq!(
+            "com.example.app.pushkit.fileprovider",
+            topic.for_push_type(PushType::FileProvider).as_str()
+        );
+    }
+
+    #[test]
+    fn test_content_state_entries() {
+        let aps = Notification::default().with_content_state_entries([
+            ("progress", Value::from(0.4)),
+            ("courier", serde_json::json!({ "name": "Sam" })),
+        ]);
+        assert_eq!(
+            serde_json::json!({
+                "content-state": { "courier": { "name": "Sam" }, "progress": 0.4 },
+            }),
+            aps.to_value().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_activity_attributes() {
+        let aps = Notification::default()
+            .with_activity_attributes("DeliveryAttributes", HashMap::from([("order", 42)]))
+            .unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "attributes-type": "DeliveryAttributes",
+                "attributes": { "order": 42 },
+            }),
+            serde_json::to_value(&aps).unwrap()
+        );
+        assert!(aps.validate().is_ok());
+
+        assert!(matches!(
+            Notification::default().with_activity_attributes("", HashMap::from([("order", 42)])),
+            Err(BuildError::IncompleteAttributesError)
+        ));
+        let aps = Notification::default()
+            .with_attributes(HashMap::from([("order", 42)]))
+            .unwrap();
+        assert!(matches!(
+            aps.validate(),
+            Err(BuildError::IncompleteAttributesError)
+        ));
+    }
+
+    #[test]
+    fn test_sound_volume() {
+        assert!(Sound::critical("siren.caf", 0.8).validate().is_ok());
+        assert!(Sound::Regular("default".to_string()).validate().is_ok());
+        for critical in [None, Some(false)] {
+            let sound = Sound::Critical {
+                critical,
+                name: Some("siren.caf".to_string()),
+                volume: Some(0.8),
+            };
+            assert!(matches!(
+                sound.validate(),
+                Err(BuildError::InvalidSoundError)
+            ));
+        }
+
+        let payload = Payload::from(Notification {
+            sound: Some(Sound::Critical {
+                critical: Some(false),
+                name: None,
+                volume: Some(0.8),
+            }),
+            ..Notification::default()
+        });
+        assert!(matches!(
+            payload.validate(),
+            Err(BuildError::InvalidSoundError)
+        ));
+    }
+
+    #[test]
+    fn test_alert_builders() {
+        let alert = Alert::Body("Body".to_string())
+            .with_title(Title::Normal("Title".to_string()))
+            .with_launch_image("launch.png");
+        assert_eq!(
+            serde_json::json!({ "title": "Title", "body": "Body", "launch-image": "launch.png" }),
+            serde_json::to_value(&alert).unwrap()
+        );
+        assert!(matches!(
+            Alert::full().validate(),
+            Err(BuildError::EmptyAlertError)
+        ));
+    }
+
+    #[test]
+    fn test_empty_alert() {
+        let empty = Alert::Full {
+            title: None,
+            subtitle: None,
+            body: None,
+            launch_image: Some("launch.png".to_string()),
+            summary_arg: None,
+            summary_arg_count: None,
+        };
+        assert!(matches!(empty.validate(), Err(BuildError::EmptyAlertError)));
+        assert!(matches!(
+            Alert::Body(String::new()).validate(),
+            Err(BuildError::EmptyAlertError)
+        ));
+
+        let payload = Payload {
+            aps: Notification {
+                alert: Some(empty),
+                ..Notification::default()
+            },
+            ..Payload::default()
+        };
+        assert!(payload.validate().is_err());
+
+        let body_only = Alert::Full {
+            title: None,
+            subtitle: None,
+            body: Some(Body::Normal("Body".to_string())),
+            launch_image: None,
+            summary_arg: None,
+            summary_arg_count: None,
+        };
+        assert!(body_only.validate().is_ok());
+        assert!(Alert::Body("Body".to_string()).validate().is_ok());
+    }
+
+    #[test]
+    fn test_deterministic_custom_order() {
+        let serialize = |keys: &[&str]| {
+            let custom = keys
+                .iter()
+                .map(|key| {
+                    (
+                        key.to_string(),
+                        serde_json::json!({ "b": 1, "a": [{ "d": 2, "c": 3 }] }),
+                    )
+                })
+                .collect::<HashMap<_, _>>();
+            let payload = Payload::default().with_custom(custom).unwrap();
+            serde_json::to_string(&payload).unwrap()
+        };
+
+        let json = serialize(&["zeta", "alpha", "mu", "beta", "omega"]);
+        assert_eq!(json, serialize(&["omega", "beta", "mu", "alpha", "zeta"]));
+        assert!(
+            json.starts_with("{\"aps\":{},\"alpha\":{\"a\":[{\"c\":3,\"d\":2}],\"b\":1},\"beta\":")
+        );
+    }
+
+    #[test]
+    fn test_prune_nulls() {
+        #[derive(Serialize)]
+        struct State {
+            score: Option<u32>,
+            players: Vec<Option<String>>,
+        }
+
+        let payload = Payload {
+            aps: Notification::default()
+                .with_content_state(State {
+                    score: None,
+                    players: vec![Some("a".to_string()), None],
+                })
+                .unwrap(),
+            ..Payload::default()
+        }
+        .with_custom(serde_json::json!({
+            "kept": 1,
+            "dropped": null,
+            "nested": { "dropped": null, "list": [{ "dropped": null, "kept": true }] },
+        }))
+        .unwrap()
+        .prune_nulls();
+
+        let json = serde_json::to_string(&payload).unwrap();
+        assert_eq!(
+            "{\"aps\":{\"content-state\":{\"players\":[\"a\",null]}},\"kept\":1,\"nested\":{\"list\":[{\"kept\":true}]}}",
+            json
+        );
+    }
+
+    #[test]
+    fn test_thread_id() {
+        let notification = Notification::default()
+            .with_thread_id("  chat-42\n")
+            .unwrap();
+        assert_eq!(Some("chat-42"), notification.thread_id.as_deref());
+
+        assert!(matches!(
+            Notification::default().with_thread_id(" \t"),
+            Err(BuildError::EmptyThreadIdError)
+        ));
+    }
+
+    struct WarnCounter(Arc<AtomicUsize>);
+
+    impl Subscriber for WarnCounter {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            if *event.metadata().level() == Level::WARN {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    /// Runs `f`, returning its result and the number of warnings it logged.
+    fn count_warnings<R>(f: impl FnOnce() -> R) -> (R, usize) {
+        let warnings = Arc::new(AtomicUsize::new(0));
+        let result = tracing::subscriber::with_default(WarnCounter(warnings.clone()), f);
+        (result, warnings.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn test_critical_relevance_warning() {
+        let (result, warnings) = count_warnings(|| {
+            Notification {
+                interruption_level: Some(InterruptionLevel::TimeSensitive),
+                relevance_score: Some(0.1),
+                ..Notification::default()
+            }
+            .validate()
+        });
+        assert!(result.is_ok());
+        assert_eq!(0, warnings);
+
+        let (result, warnings) = count_warnings(|| {
+            Notification {
+                interruption_level: Some(InterruptionLevel::Critical),
+                relevance_score: Some(1.0),
+                ..Notification::default()
+            }
+            .validate()
+        });
+        assert!(result.is_ok());
+        assert_eq!(0, warnings);
+
+        let (result, warnings) = count_warnings(|| {
+            Notification {
+                interruption_level: Some(InterruptionLevel::Critical),
+                relevance_score: Some(0.1),
+                ..Notification::default()
+            }
+            .validate()
+        });
+        assert!(result.is_ok());
+        assert_eq!(1, warnings);
+    }
+
+    #[test]
+    fn test_live_activity_update_diff() {
+        let previous = serde_json::json!({ "home": 1, "away": 0, "clock": "12:00" });
+        let state = serde_json::json!({ "home": 1, "away": 1 });
+        let notification =
+            Notification::live_activity_update_diff(&previous, &state, 1_700_000_000).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "content-state": { "away": 1, "clock": null },
+                "event": "update",
+                "timestamp": 1_700_000_000,
+            }),
+            serde_json::to_value(&notification).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_live_activity_update() {
+        #[derive(Serialize)]
+        struct Score {
+            home: u32,
+            away: u32,
+        }
+
+        let previous = Score { home: 1, away: 0 };
+        let (notification, warnings) = count_warnings(|| {
+            Notification::live_activity_update(
+                &previous,
+                &Score { home: 1, away: 1 },
+                1_700_000_000,
+            )
+        });
+        let notification = notification.unwrap();
+        assert_eq!(0, warnings);
+        assert_eq!(
+            serde_json::json!({
+                "content-state": { "away": 1, "home": 1 },
+                "event": "update",
+                "timestamp": 1_700_000_000,
+            }),
+            serde_json::to_value(&notification).unwrap()
+        );
+
+        let (notification, warnings) = count_warnings(|| {
+            Notification::live_activity_update(
+                &previous,
+                &Score { home: 1, away: 0 },
+                1_700_000_001,
+            )
+        });
+        assert!(notification.is_ok());
+        assert_eq!(1, warnings);
+    }
+
+    #[test]
+    fn test_live_activity_end() {
+        let dismissal_date = |policy| {
+            let notification =
+                Notification::live_activity_end(&HashMap::from([("done", true)]), 100, policy)
+                    .unwrap();
+            let value = serde_json::to_value(&notification).unwrap();
+            assert_eq!("end", value["event"]);
+            value.get("dismissal-date").cloned()
+        };
+
+        assert_eq!(None, dismissal_date(DismissalPolicy::Default));
+        assert_eq!(
+            Some(serde_json::json!(0)),
+            dismissal_date(DismissalPolicy::Immediate)
+        );
+        assert_eq!(
+            Some(serde_json::json!(3_700)),
+            dismissal_date(DismissalPolicy::At(3_700))
+        );
+    }
 }